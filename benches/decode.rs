@@ -0,0 +1,94 @@
+//! Compares the ways a large page of articles can be decoded:
+//!
+//! * `buffered` - the body as a `String`, then `Articles` (what `send_*` does)
+//! * `streaming` - `for_each_article` reading straight from the body
+//! * `in_slice` - `for_each_article_in_slice` on the buffered body (the async path)
+//! * `borrowed` - `ArticlesRef` borrowing from the body
+//!
+//! Run with `cargo bench --bench decode`.
+
+use newsapi::payload::article::Articles;
+use newsapi::payload::borrowed::ArticlesRef;
+use newsapi::payload::stream::{for_each_article, for_each_article_in_slice};
+use serde_json::Value;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ARTICLES_PER_PAGE: usize = 100;
+const PAGES: usize = 200;
+
+fn large_page() -> Vec<u8> {
+    let example: Value =
+        serde_json::from_str(include_str!("../resources/example_everything.json")).unwrap();
+    let articles = &example["articles"];
+
+    let page: Vec<Value> = (0..ARTICLES_PER_PAGE)
+        .map(|i| {
+            let mut article = articles[i % 2].clone();
+            article["url"] = Value::String(format!("https://example.com/story/{i}"));
+            article
+        })
+        .collect();
+
+    serde_json::to_vec(&serde_json::json!({
+        "status": "ok",
+        "totalResults": ARTICLES_PER_PAGE,
+        "articles": page,
+    }))
+    .unwrap()
+}
+
+fn time<F: FnMut() -> usize>(name: &str, body_len: usize, mut decode: F) {
+    let mut total = Duration::ZERO;
+    for _ in 0..PAGES {
+        let started = Instant::now();
+        assert_eq!(black_box(decode()), ARTICLES_PER_PAGE);
+        total += started.elapsed();
+    }
+
+    let per_page = total / PAGES as u32;
+    let throughput = body_len as f64 / per_page.as_secs_f64() / 1_000_000.0;
+    println!("{name:>10}: {per_page:>10.2?} per page, {throughput:>8.1} MB/s");
+}
+
+fn main() {
+    let body = large_page();
+    println!(
+        "{} pages of {} articles, {} bytes each",
+        PAGES,
+        ARTICLES_PER_PAGE,
+        body.len()
+    );
+
+    time("buffered", body.len(), || {
+        let text = String::from_utf8(body.clone()).unwrap();
+        serde_json::from_str::<Articles>(&text)
+            .unwrap()
+            .articles
+            .len()
+    });
+
+    time("streaming", body.len(), || {
+        let mut count = 0;
+        for_each_article(&body[..], |article| {
+            black_box(article);
+            count += 1;
+        })
+        .unwrap();
+        count
+    });
+
+    time("in_slice", body.len(), || {
+        let mut count = 0;
+        for_each_article_in_slice(&body, |article| {
+            black_box(article);
+            count += 1;
+        })
+        .unwrap();
+        count
+    });
+
+    time("borrowed", body.len(), || {
+        ArticlesRef::from_slice(&body).unwrap().articles.len()
+    });
+}
@@ -4,18 +4,14 @@ use newsapi::api::NewsAPIClient;
 use newsapi::constants::{Category, Language, SortMethod};
 use newsapi::payload::article::Articles;
 
-use std::env;
-
 fn main() {
-    let key = env::var("NEWSAPI_KEY").unwrap();
-
     let start_timestamp = Utc::now() - Duration::days(10);
     let end_timestamp = Utc::now();
 
     // create a client
-    let mut c = NewsAPIClient::new(key);
+    let mut c = NewsAPIClient::from_env().unwrap();
 
-    c
+    let request = c
         // Search German news sources for articles
         .language(Language::German)
         // In the last ten days
@@ -27,14 +23,14 @@ fn main() {
         .category(Category::General)
         // Sort by the most popular articles
         .sort_by(SortMethod::Popularity)
-        // get articles from the everything endpoint - this builds the url
+        // get articles from the everything endpoint - the url is built when the request is sent
         .everything();
 
-    // debug print the current client status - you can see the URL that will be sent to the API
-    println!("{:?}", c);
+    // print the URL that will be sent to the API
+    println!("{}", request.url());
 
-    // fire off a request to the endpoint and deserialize the results into an Article struct
-    let articles = c.send_sync::<Articles>().unwrap();
+    // fire off a request to the endpoint, the everything endpoint returns Articles
+    let articles: Articles = request.send_sync().unwrap();
 
     // print the results to the terminal
     println!("{:?}", articles);
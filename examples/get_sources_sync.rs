@@ -1,17 +1,13 @@
 use newsapi::api::NewsAPIClient;
 use newsapi::constants::Language;
-use newsapi::payload::source::Sources;
-
-use std::env;
 
 fn main() {
-    let key = env::var("NEWSAPI_KEY").unwrap();
-
     // search for English language Sources
-    let sources = NewsAPIClient::new(key)
+    let sources = NewsAPIClient::from_env()
+        .unwrap()
         .language(Language::English)
         .sources()
-        .send_sync::<Sources>();
+        .send_sync();
 
     println!(
         "{}",
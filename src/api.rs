@@ -1,16 +1,76 @@
+use super::cancel::{unless_cancelled, CancellationToken};
+use super::circuit::{CircuitBreaker, CircuitState, Permit};
 use super::constants;
+use super::endpoint::{self, Endpoint};
 use super::error::NewsApiError;
+#[cfg(feature = "blocking")]
+use super::execute::block_on;
+#[cfg(feature = "async")]
+use super::execute::AsyncFlavour;
+#[cfg(feature = "blocking")]
+use super::execute::BlockingFlavour;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use super::execute::Counted;
+use super::execute::Flavour;
+use super::explain::Explanation;
+use super::fingerprint::Fingerprint;
+use super::key::{redact_url, ApiKey, AuthMode, KeyPool};
+use super::metrics::Metrics;
+use super::middleware::{self, AfterResponse, BeforeRequest, Flow, Middleware};
+use super::payload::status::{ApiError, ResponseStatus, StatusProbe};
+#[cfg(feature = "blocking")]
+use super::payload::stream::for_each_article;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use super::payload::stream::for_each_article_in_slice;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use super::payload::{article::Article, stream::ArticlesSummary};
+use super::request::Request;
+use super::response::Response;
+#[cfg(feature = "async")]
+use super::single_flight::SingleFlight;
+use super::spec::RequestSpec;
+use super::stale::{MaybeStale, StaleCache};
+use super::trace::{self, RequestSpan};
+#[cfg(feature = "async")]
+use super::transport::AsyncTransport;
+#[cfg(feature = "blocking")]
+use super::transport::BlockingTransport;
+use super::transport::{HttpRequest, HttpResponse, ReqwestTransport, Transport};
 use chrono::prelude::*;
 use serde::de::DeserializeOwned;
+#[cfg(feature = "blocking")]
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt::Display;
+#[cfg(feature = "blocking")]
+use std::io::Read;
+use std::path::Path;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 
 #[derive(Debug)]
 pub struct NewsAPIClient {
-    api_key: String,
+    api_key: ApiKey,
     parameters: HashMap<String, String>,
-    url: Option<String>,
+    stale: Option<StaleCache>,
+    metrics: Option<Arc<Metrics>>,
+    key_pool: Option<Arc<KeyPool>>,
+    auth_mode: AuthMode,
+    timeout: Option<Duration>,
+    deadline: Option<Duration>,
+    cancellation: Option<CancellationToken>,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    #[cfg(feature = "async")]
+    single_flight: Option<Arc<SingleFlight>>,
+    #[cfg(feature = "async")]
+    transport: Arc<dyn AsyncTransport>,
+    #[cfg(feature = "blocking")]
+    blocking_transport: Arc<dyn BlockingTransport>,
 }
 
 impl NewsAPIClient {
@@ -18,107 +78,819 @@ impl NewsAPIClient {
     ///
     /// # Arguments
     ///
-    /// * `api_key` - a string that holds the api, this will be used to set X-Api-Key.
+    /// * `api_key` - a string or `ApiKey` that holds the api, this will be used to set X-Api-Key.
     ///
-    pub fn new(api_key: String) -> NewsAPIClient {
+    pub fn new(api_key: impl Into<ApiKey>) -> NewsAPIClient {
+        #[allow(unused_variables)]
+        let transport = Arc::new(ReqwestTransport::new());
         NewsAPIClient {
-            api_key,
+            api_key: api_key.into(),
             parameters: HashMap::new(),
-            url: None,
-        }
-    }
-
-    /// Build the 'fetch everything' url
-    pub fn everything(&mut self) -> &mut NewsAPIClient {
-        let allowed_params = vec![
-            "q",
-            "sources",
-            "domains",
-            "excludeDomains",
-            "from",
-            "to",
-            "language",
-            "sortBy",
-            "pageSize",
-            "page",
-        ];
-
-        self.url = Some(self.build_url(constants::EVERYTHING_URL, allowed_params));
+            stale: None,
+            metrics: None,
+            key_pool: None,
+            auth_mode: AuthMode::default(),
+            timeout: None,
+            deadline: None,
+            cancellation: None,
+            circuit_breaker: None,
+            middleware: vec![],
+            #[cfg(feature = "async")]
+            single_flight: None,
+            #[cfg(feature = "async")]
+            transport: transport.clone(),
+            #[cfg(feature = "blocking")]
+            blocking_transport: transport,
+        }
+    }
+
+    /// A client using the key in the `NEWSAPI_KEY` environment variable
+    pub fn from_env() -> Result<NewsAPIClient, NewsApiError> {
+        Ok(NewsAPIClient::new(ApiKey::from_env()?))
+    }
+
+    /// A client using the key stored in the file at `path`
+    pub fn from_key_file(path: impl AsRef<Path>) -> Result<NewsAPIClient, NewsApiError> {
+        Ok(NewsAPIClient::new(ApiKey::from_file(path)?))
+    }
+
+    /// A client using the key returned by `provider`, e.g. a secret manager lookup
+    pub fn from_key_provider<F, E>(provider: F) -> Result<NewsAPIClient, NewsApiError>
+    where
+        F: FnOnce() -> Result<String, E>,
+        E: Display,
+    {
+        Ok(NewsAPIClient::new(ApiKey::from_provider(provider)?))
+    }
+
+    /// Send requests through `transport` instead of the default reqwest based one, e.g. a
+    /// `MockTransport` in tests or an instrumented in-house HTTP stack.
+    #[allow(unused_variables)]
+    pub fn transport<T>(&mut self, transport: Arc<T>) -> &mut NewsAPIClient
+    where
+        T: Transport + 'static,
+    {
+        #[cfg(feature = "async")]
+        {
+            self.transport = transport.clone();
+        }
+        #[cfg(feature = "blocking")]
+        {
+            self.blocking_transport = transport;
+        }
         self
     }
 
-    /// Build the 'top_headlines' url
-    pub fn top_headlines(&mut self) -> &mut NewsAPIClient {
-        let allowed_params = vec!["q", "country", "category", "sources", "pageSize", "page"];
-        self.url = Some(self.build_url(constants::TOP_HEADLINES_URL, allowed_params));
+    /// Where requests carry the API key, the `X-Api-Key` header unless set otherwise
+    pub fn auth_mode(&mut self, auth_mode: AuthMode) -> &mut NewsAPIClient {
+        self.auth_mode = auth_mode;
         self
     }
 
-    /// Build the 'sources' url
-    pub fn sources(&mut self) -> &mut NewsAPIClient {
-        let allowed_params = vec!["category", "language", "country"];
-        self.url = Some(self.build_url(constants::SOURCES_URL, allowed_params));
+    /// Give up on any single request to NewsAPI that takes longer than `timeout`
+    pub fn timeout(&mut self, timeout: Duration) -> &mut NewsAPIClient {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Give up on a send that takes longer than `deadline` overall, including the attempts
+    /// made with other keys of a key pool. A sync fetch is held to it across all its pages.
+    pub fn deadline(&mut self, deadline: Duration) -> &mut NewsAPIClient {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Fail sends with `NewsApiError::Cancelled` once `token` is cancelled
+    pub fn cancel_with(&mut self, token: CancellationToken) -> &mut NewsAPIClient {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Stop calling NewsAPI while it is down, see `CircuitBreaker`. Share one breaker between
+    /// the clients of a service so they all back off together.
+    pub fn circuit_breaker(&mut self, breaker: Arc<CircuitBreaker>) -> &mut NewsAPIClient {
+        self.circuit_breaker = Some(breaker);
+        self
+    }
+
+    /// Let concurrent identical `send_async` requests share one call to NewsAPI, see
+    /// `SingleFlight`
+    #[cfg(feature = "async")]
+    pub fn single_flight(&mut self, flight: Arc<SingleFlight>) -> &mut NewsAPIClient {
+        self.single_flight = Some(flight);
+        self
+    }
+
+    /// The state of the circuit breaker, if there is one, e.g. for a health check
+    pub fn circuit_state(&self) -> Option<CircuitState> {
+        self.circuit_breaker.as_ref().map(|breaker| breaker.state())
+    }
+
+    /// Send requests with the keys in `pool` instead of the client's own key, failing over to
+    /// the next one when a key is rejected, rate limited or out of quota
+    pub fn key_pool(&mut self, pool: Arc<KeyPool>) -> &mut NewsAPIClient {
+        self.key_pool = Some(pool);
+        self
+    }
+
+    /// Record request counts, latencies, errors and quota use in `metrics`
+    pub fn metrics(&mut self, metrics: Arc<Metrics>) -> &mut NewsAPIClient {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Add `middleware` to the end of the chain run around every request
+    pub fn middleware<M>(&mut self, middleware: M) -> &mut NewsAPIClient
+    where
+        M: Middleware + 'static,
+    {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Add a hook that can change the URL and headers of every request, or answer it without
+    /// going to NewsAPI by returning `Flow::Respond`
+    pub fn before_request<F>(&mut self, hook: F) -> &mut NewsAPIClient
+    where
+        F: Fn(&mut HttpRequest) -> Result<Flow, NewsApiError> + Send + Sync + 'static,
+    {
+        self.middleware(BeforeRequest(hook))
+    }
+
+    /// Add a hook that sees every response before it is checked and decoded, and may replace it
+    pub fn after_response<F>(&mut self, hook: F) -> &mut NewsAPIClient
+    where
+        F: Fn(&HttpRequest, HttpResponse) -> Result<HttpResponse, NewsApiError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.middleware(AfterResponse(hook))
+    }
+
+    /// A request to the everything endpoint. Sending it yields `Articles`.
+    pub fn everything(&self) -> Request<'_, endpoint::Everything> {
+        self.endpoint::<endpoint::Everything>()
+    }
+
+    /// A request to the top headlines endpoint. Sending it yields `Articles`.
+    pub fn top_headlines(&self) -> Request<'_, endpoint::TopHeadlines> {
+        self.endpoint::<endpoint::TopHeadlines>()
+    }
+
+    /// A request to the sources endpoint. Sending it yields `Sources`.
+    pub fn sources(&self) -> Request<'_, endpoint::Sources> {
+        self.endpoint::<endpoint::Sources>()
+    }
+
+    /// Replace the parameters with those of `spec`. `Request::from_spec` does this and picks
+    /// the endpoint, checking it is the one the spec is for and that NewsAPI accepts its
+    /// values; here they are sent as they are.
+    pub fn request_spec(&mut self, spec: &RequestSpec) -> &mut NewsAPIClient {
+        self.parameters = spec_parameters(spec);
         self
     }
 
-    /// Send the constructed URL to the newsapi server
-    pub async fn send_async<T>(&self) -> Result<T, NewsApiError>
+    pub(crate) fn parameters(&self) -> &HashMap<String, String> {
+        &self.parameters
+    }
+
+    fn endpoint<E: Endpoint>(&self) -> Request<'_, E> {
+        Request::new(self)
+    }
+
+    /// The URL for endpoint `E` with the current parameters
+    pub(crate) fn url_for<E: Endpoint>(&self) -> String {
+        build_url(E::URL, &self.parameters, E::PARAMETERS)
+    }
+
+    /// Describe the request a send to `url` would make, without sending it
+    pub(crate) fn explain(&self, url: &str) -> Explanation {
+        self.explain_parameters(url, &self.parameters, vec![])
+    }
+
+    /// Describe the request a send of `spec` would make, without sending it
+    pub(crate) fn explain_spec(&self, spec: &RequestSpec) -> Explanation {
+        let parameters = spec_parameters(spec);
+        let url = spec.endpoint_url();
+        let allowed = endpoint::parameters_of(url).unwrap_or_default();
+        let refusals = spec.validate().err().into_iter().collect();
+        self.explain_parameters(&build_url(url, &parameters, allowed), &parameters, refusals)
+    }
+
+    /// `refusals` are errors a send fails with before calling NewsAPI, on top of the client's
+    fn explain_parameters(
+        &self,
+        url: &str,
+        parameters: &HashMap<String, String>,
+        mut refusals: Vec<NewsApiError>,
+    ) -> Explanation {
+        let request = self.prepare_request(url, &self.api_key, self.timeout);
+        refusals.extend(self.refusals(parameters));
+        let mut warnings = self.warnings(url, parameters);
+        warnings.extend(refusals.iter().map(ToString::to_string));
+
+        // a send that fails before calling NewsAPI makes no requests at all
+        let (http_requests, max_http_requests) = if refusals.is_empty() {
+            let keys = self.key_pool.as_ref().map_or(1, |pool| pool.len().max(1));
+            (1, keys)
+        } else {
+            (0, 0)
+        };
+        Explanation::new(
+            url,
+            &request,
+            self.auth_mode,
+            warnings,
+            http_requests,
+            max_http_requests,
+        )
+    }
+
+    fn warnings(&self, url: &str, parameters: &HashMap<String, String>) -> Vec<String> {
+        let mut warnings = vec![];
+        let name = endpoint::name_of(url);
+        let url = url.split_once('?').map_or(url, |(base, _)| base);
+        let allowed = endpoint::parameters_of(url).unwrap_or_default();
+
+        let mut ignored: Vec<_> = parameters
+            .keys()
+            .filter(|parameter| !allowed.contains(&parameter.as_str()))
+            .collect();
+        ignored.sort();
+        for parameter in ignored {
+            warnings.push(format!("{parameter} is not sent, {name} does not take it"));
+        }
+
+        let required: &[&str] = match name {
+            "everything" => &["q", "sources", "domains"],
+            "top-headlines" => &["q", "country", "category", "sources"],
+            _ => &[],
+        };
+        if !required.is_empty() && !required.iter().any(|p| parameters.contains_key(*p)) {
+            warnings.push(format!(
+                "{name} needs one of {}, NewsAPI will answer parametersMissing",
+                required.join(", ")
+            ));
+        }
+        warnings
+    }
+
+    /// The errors a send with `parameters` would fail with before calling NewsAPI
+    fn refusals(&self, parameters: &HashMap<String, String>) -> Vec<NewsApiError> {
+        let mut refusals = vec![];
+        if invalid_arguments_specified(parameters) {
+            refusals.push(NewsApiError::InvalidParameterCombinationError);
+        }
+        if self.circuit_state() == Some(CircuitState::Open) {
+            refusals.push(NewsApiError::CircuitOpen);
+        }
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+        {
+            refusals.push(NewsApiError::Cancelled);
+        }
+        refusals
+    }
+
+    /// Keep the last good response for each request and, via `send_async_or_stale` and
+    /// `send_sync_or_stale`, serve it in place of quota or server errors for up to
+    /// `max_staleness`.
+    pub fn stale_while_error(&mut self, max_staleness: Duration) -> &mut NewsAPIClient {
+        self.stale = Some(StaleCache::new(max_staleness));
+        self
+    }
+
+    /// Like `stale_while_error`, with a cache set up beforehand, e.g. to change its capacity
+    pub fn stale_cache(&mut self, cache: StaleCache) -> &mut NewsAPIClient {
+        self.stale = Some(cache);
+        self
+    }
+
+    /// Send `url` to the newsapi server, decoding the response into any type
+    #[cfg(feature = "async")]
+    pub(crate) async fn send_async<T>(&self, url: &str) -> Result<T, NewsApiError>
     where
         T: DeserializeOwned,
     {
-        if self.invalid_arguments_specified() {
-            return Err(NewsApiError::InvalidParameterCombinationError);
+        let response = self.execute_async(url).await?;
+        NewsAPIClient::decode(&response.body)
+    }
+
+    /// Send `url` to the newsapi server, decoding the response into any type
+    #[cfg(feature = "blocking")]
+    pub(crate) fn send_sync<T>(&self, url: &str) -> Result<T, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = block_on(self.execute(&BlockingFlavour(&*self.blocking_transport), url))?;
+        NewsAPIClient::decode(&response.body)
+    }
+
+    /// Like `send_async`, but when NewsAPI is unavailable and `stale_while_error` is configured
+    /// the last good response is returned, marked with its age and the error it replaces.
+    #[cfg(feature = "async")]
+    pub(crate) async fn send_async_or_stale<T>(
+        &self,
+        url: &str,
+    ) -> Result<MaybeStale<T>, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        self.or_stale(self.execute_async(url).await, url)
+    }
+
+    /// Like `send_sync`, but when NewsAPI is unavailable and `stale_while_error` is configured
+    /// the last good response is returned, marked with its age and the error it replaces.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn send_sync_or_stale<T>(&self, url: &str) -> Result<MaybeStale<T>, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        let result = block_on(self.execute(&BlockingFlavour(&*self.blocking_transport), url));
+        self.or_stale(result, url)
+    }
+
+    /// Like `send_async`, but the payload comes wrapped in a `Response` along with the HTTP
+    /// status, headers, timing, fingerprint and raw body.
+    #[cfg(feature = "async")]
+    pub(crate) async fn send_async_with_meta<T>(
+        &self,
+        url: &str,
+    ) -> Result<Response<T>, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        self.execute_async(url).await?.decode::<T>()
+    }
+
+    /// Like `send_sync`, but the payload comes wrapped in a `Response` along with the HTTP
+    /// status, headers, timing, fingerprint and raw body.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn send_sync_with_meta<T>(&self, url: &str) -> Result<Response<T>, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        block_on(self.execute(&BlockingFlavour(&*self.blocking_transport), url))?.decode::<T>()
+    }
+
+    /// Decode the articles in the response one at a time as the body is read from the
+    /// transport, see `payload::stream::for_each_article`
+    #[cfg(feature = "blocking")]
+    pub(crate) fn for_each_article_sync<F>(
+        &self,
+        url: &str,
+        f: F,
+    ) -> Result<ArticlesSummary, NewsApiError>
+    where
+        F: FnMut(Article),
+    {
+        // after_response hooks need the whole response and failing over needs the pipeline,
+        // so only stream without middleware or a key pool
+        if !self.middleware.is_empty() || self.key_pool.is_some() {
+            let response =
+                block_on(self.execute(&BlockingFlavour(&*self.blocking_transport), url))?;
+            return Ok(for_each_article_in_slice(&response.body, f)?);
+        }
+
+        self.check_parameters()?;
+        let span = RequestSpan::new(url, 1);
+        let started = Instant::now();
+        let permit = self.circuit_permit()?;
+        let result = self.stream_articles(url, &span, f);
+        if let Some(permit) = permit {
+            permit.record(&result);
+        }
+        self.record_quota(&result);
+        self.observe(url, &span, started, result)
+    }
+
+    #[cfg(feature = "blocking")]
+    fn stream_articles<F>(
+        &self,
+        url: &str,
+        span: &RequestSpan,
+        f: F,
+    ) -> Result<ArticlesSummary, NewsApiError>
+    where
+        F: FnMut(Article),
+    {
+        let started = Instant::now();
+        let timeout = self.attempt_timeout(started)?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_quota_use();
+        }
+        let mut stream = self
+            .blocking_transport
+            .execute_reader(self.prepare_request(url, &self.api_key, timeout))?;
+        span.record_response(stream.status, started.elapsed());
+
+        if !stream.is_success() {
+            let mut body = vec![];
+            stream
+                .body
+                .read_to_end(&mut body)
+                .map_err(|error| NewsApiError::TransportError {
+                    message: error.to_string(),
+                })?;
+            let text = String::from_utf8_lossy(&body).into_owned();
+            return Err(NewsAPIClient::handle_api_error(stream.status, text));
+        }
+
+        // keep a copy of the body as it goes past: all of it for the stale cache, like every
+        // other send, otherwise only until the first article so an error body, which has none,
+        // is reported as it was sent
+        let keep = self.stale.is_some();
+        let copy = RefCell::new(Some(vec![]));
+        let body = Tee {
+            reader: stream.body,
+            copy: &copy,
+        };
+        let mut f = f;
+        let summary = for_each_article(body, |article| {
+            if !keep {
+                copy.replace(None);
+            }
+            f(article)
+        })?;
+        let body = copy.into_inner().unwrap_or_default();
+        if summary.status == Some(ResponseStatus::Error) {
+            return Err(NewsAPIClient::body_error(&body));
+        }
+        if keep {
+            self.remember(url, &body);
+        }
+        Ok(summary)
+    }
+
+    /// Decode the articles in the response one at a time. This does not stream: the async
+    /// transport hands over the whole body, which is then decoded without building a
+    /// `Vec<Article>`.
+    #[cfg(feature = "async")]
+    pub(crate) async fn for_each_article_async<F>(
+        &self,
+        url: &str,
+        f: F,
+    ) -> Result<ArticlesSummary, NewsApiError>
+    where
+        F: FnMut(Article),
+    {
+        let response = self.execute_async(url).await?;
+        Ok(for_each_article_in_slice(&response.body, f)?)
+    }
+
+    /// The execution pipeline shared by every `send_*` method: validate, build the request, run
+    /// the middleware around the transport, map errors and keep the stale cache up to date.
+    async fn execute<F: Flavour>(
+        &self,
+        flavour: &F,
+        url: &str,
+    ) -> Result<Response<()>, NewsApiError> {
+        self.check_parameters()?;
+        self.execute_url(flavour, url, Instant::now()).await
+    }
+
+    /// `execute` through the async transport, sharing the call with identical requests in
+    /// flight when there is a `SingleFlight`
+    #[cfg(feature = "async")]
+    async fn execute_async(&self, url: &str) -> Result<Response<()>, NewsApiError> {
+        let flavour = AsyncFlavour(&*self.transport);
+        let flight = match &self.single_flight {
+            Some(flight) => flight,
+            None => return self.execute(&flavour, url).await,
+        };
+
+        self.check_parameters()?;
+        // waiting on an identical request is held to this request's own timeout and deadline
+        let started = Instant::now();
+        let deadline = self
+            .attempt_timeout(started)?
+            .map(|timeout| started + timeout);
+        unless_cancelled(self.cancellation.as_ref(), async {
+            let (result, shared) = flight
+                .run(
+                    Fingerprint::of_url(url),
+                    deadline,
+                    self.execute_url(&flavour, url, started),
+                )
+                .await;
+            if let (true, Some(metrics)) = (shared, &self.metrics) {
+                metrics.record_coalesced();
+            }
+            result
+        })
+        .await
+    }
+
+    /// `execute` for a URL whose parameters were already checked, held to the deadline of a
+    /// send that began at `started`
+    async fn execute_url<F: Flavour>(
+        &self,
+        flavour: &F,
+        url: &str,
+        started: Instant,
+    ) -> Result<Response<()>, NewsApiError> {
+        let pool = match &self.key_pool {
+            Some(pool) => pool,
+            None => {
+                let timeout = self.attempt_timeout(started)?;
+                let permit = self.circuit_permit()?;
+                let result = self
+                    .attempt(flavour, url, permit, &self.api_key, timeout, 1)
+                    .await;
+                self.record_quota(&result);
+                return result;
+            }
+        };
+
+        // fail over to the next key until one works, every key has been tried or the deadline
+        // has passed. The circuit is asked first so a request it refuses does not use up a key.
+        let mut attempt = 1;
+        let mut failed = None;
+        loop {
+            let next = self.attempt_timeout(started).and_then(|timeout| {
+                let permit = self.circuit_permit()?;
+                let (index, key) = pool.acquire()?;
+                Ok((timeout, permit, index, key))
+            });
+            let (timeout, permit, index, key) = match (next, failed.take()) {
+                (Ok(next), _) => next,
+                // no key is left to fail over to: report why the last one failed
+                (Err(NewsApiError::ApiKeyError { .. }), Some(error)) => {
+                    let result = Err(error);
+                    self.record_quota(&result);
+                    return result;
+                }
+                (Err(error), _) => return Err(error),
+            };
+            let result = self
+                .attempt(flavour, url, permit, &key, timeout, attempt)
+                .await;
+            match result {
+                Err(error) if attempt < pool.len() && pool.fail_over(index, &error) => {
+                    trace::key_failover(&pool.label(index), attempt, &error);
+                    failed = Some(error);
+                    attempt += 1;
+                }
+                Err(error) => {
+                    pool.fail_over(index, &error);
+                    let result = Err(error);
+                    self.record_quota(&result);
+                    return result;
+                }
+                Ok(_) => return result,
+            }
+        }
+    }
+
+    /// Send the request once with `key`, as allowed by the circuit breaker's `permit`
+    async fn attempt<F: Flavour>(
+        &self,
+        flavour: &F,
+        url: &str,
+        permit: Option<Permit<'_>>,
+        key: &ApiKey,
+        timeout: Option<Duration>,
+        attempt: usize,
+    ) -> Result<Response<()>, NewsApiError> {
+        let span = RequestSpan::new(url, attempt as u32);
+        let started = Instant::now();
+
+        let request = self.prepare_request(url, key, timeout);
+        let result = span
+            .instrument(self.execute_traced(flavour, url, request, &span))
+            .await;
+        if let Some(permit) = permit {
+            permit.record(&result);
         }
+        self.observe(url, &span, started, result)
+    }
+
+    fn circuit_permit(&self) -> Result<Option<Permit<'_>>, NewsApiError> {
+        self.circuit_breaker
+            .as_ref()
+            .map(|breaker| breaker.permit())
+            .transpose()
+    }
+
+    /// How long the next attempt of a send that began at `started` may take, failing if the
+    /// send was cancelled or its deadline has passed
+    fn attempt_timeout(&self, started: Instant) -> Result<Option<Duration>, NewsApiError> {
+        if let Some(token) = &self.cancellation {
+            token.check()?;
+        }
+
+        let remaining = match self.deadline {
+            Some(deadline) => match deadline.checked_sub(started.elapsed()) {
+                Some(remaining) if !remaining.is_zero() => Some(remaining),
+                _ => return Err(NewsApiError::Timeout),
+            },
+            None => None,
+        };
 
-        match &self.url {
-            Some(url) => {
-                let body = NewsAPIClient::fetch_resource_async(url, &self.api_key).await?;
-                Ok(serde_json::from_str::<T>(&body)?)
+        Ok(match (self.timeout, remaining) {
+            (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+            (timeout, remaining) => timeout.or(remaining),
+        })
+    }
+
+    /// Report how a request went to the span and the metrics
+    fn observe<T>(
+        &self,
+        url: &str,
+        span: &RequestSpan,
+        started: Instant,
+        result: Result<T, NewsApiError>,
+    ) -> Result<T, NewsApiError> {
+        if let Err(error) = &result {
+            span.record_error(error);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_request(endpoint::name_of(url), &result, started.elapsed());
+        }
+        result
+    }
+
+    async fn execute_traced<F: Flavour>(
+        &self,
+        flavour: &F,
+        url: &str,
+        mut request: HttpRequest,
+        span: &RequestSpan,
+    ) -> Result<Response<()>, NewsApiError> {
+        let started = Instant::now();
+        let resp = match middleware::before_request(&self.middleware, &mut request)? {
+            Some(resp) => resp,
+            None => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.record_quota_use();
+                }
+                unless_cancelled(self.cancellation.as_ref(), flavour.execute(request.clone()))
+                    .await?
             }
-            None => Err(NewsApiError::UndefinedUrlError),
+        };
+        let elapsed = started.elapsed();
+
+        let resp = middleware::after_response(&self.middleware, &request, resp)?;
+        span.record_response(resp.status, elapsed);
+        let resp = NewsAPIClient::handle_response(resp)?;
+        self.remember(url, &resp.body);
+
+        Ok(Response {
+            payload: (),
+            status: resp.status,
+            headers: resp.headers,
+            elapsed,
+            url: redact_url(resp.url.as_deref().unwrap_or(&request.url)),
+            fingerprint: Fingerprint::of_url(url),
+            body: resp.body,
+        })
+    }
+
+    /// Decode the response to `url`, or fall back to the stale cache if the send failed
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn or_stale<T>(
+        &self,
+        result: Result<Response<()>, NewsApiError>,
+        url: &str,
+    ) -> Result<MaybeStale<T>, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        match result {
+            Ok(response) => Ok(MaybeStale::fresh(NewsAPIClient::decode(&response.body)?)),
+            Err(error) => self.fall_back(error, url),
         }
     }
 
-    /// Send the constructed URL to the newsapi server
-    pub fn send_sync<T>(&self) -> Result<T, NewsApiError>
+    fn decode<T>(body: &[u8]) -> Result<T, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(serde_json::from_slice::<T>(body)?)
+    }
+
+    /// Send the request described by `spec` rather than the client's own parameters, counting
+    /// the HTTP requests made in `sent`. The deadline runs from `started`, so it can cover
+    /// several sends.
+    #[cfg(feature = "async")]
+    pub(crate) async fn send_spec_async<T>(
+        &self,
+        spec: &RequestSpec,
+        sent: &AtomicUsize,
+        started: Instant,
+    ) -> Result<T, NewsApiError>
     where
         T: DeserializeOwned,
     {
-        if self.invalid_arguments_specified() {
+        let url = NewsAPIClient::spec_url(spec)?;
+        let flavour = Counted(AsyncFlavour(&*self.transport), sent);
+        let response = self.execute_url(&flavour, &url, started).await?;
+        NewsAPIClient::decode(&response.body)
+    }
+
+    /// Send the request described by `spec` rather than the client's own parameters, counting
+    /// the HTTP requests made in `sent`. The deadline runs from `started`, so it can cover
+    /// several sends.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn send_spec_sync<T>(
+        &self,
+        spec: &RequestSpec,
+        sent: &AtomicUsize,
+        started: Instant,
+    ) -> Result<T, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        let url = NewsAPIClient::spec_url(spec)?;
+        let flavour = Counted(BlockingFlavour(&*self.blocking_transport), sent);
+        let response = block_on(self.execute_url(&flavour, &url, started))?;
+        NewsAPIClient::decode(&response.body)
+    }
+
+    /// The URL for `spec`, checked as a send with its parameters would be
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn spec_url(spec: &RequestSpec) -> Result<String, NewsApiError> {
+        spec.validate()?;
+        let parameters = spec_parameters(spec);
+        if invalid_arguments_specified(&parameters) {
             return Err(NewsApiError::InvalidParameterCombinationError);
         }
+        let url = spec.endpoint_url();
+        let allowed = endpoint::parameters_of(url).unwrap_or_default();
+        Ok(build_url(url, &parameters, allowed))
+    }
 
-        match &self.url {
-            Some(url) => {
-                let body = NewsAPIClient::fetch_resource_sync(url, &self.api_key)?;
-                Ok(serde_json::from_str::<T>(&body)?)
+    /// True once the daily quota tracked by the client's metrics is used up
+    pub(crate) fn quota_spent(&self) -> bool {
+        self.quota_remaining() == Some(0)
+    }
+
+    /// True if `result` says the daily quota is used up: NewsAPI answered `apiKeyExhausted`
+    /// and, with a key pool, it has done so for every key
+    pub(crate) fn out_of_quota<T>(&self, result: &Result<T, NewsApiError>) -> bool {
+        match result {
+            Err(error) if error.exhausts_quota() => {
+                self.key_pool.as_ref().is_none_or(|pool| pool.exhausted())
             }
-            None => Err(NewsApiError::UndefinedUrlError),
+            _ => false,
         }
     }
 
-    fn build_url(&self, base_url: &str, allowed_params: Vec<&str>) -> String {
-        let mut params: Vec<String> = vec![];
-        for field in allowed_params {
-            if let Some(value) = self.parameters.get(field) {
-                params.push(format!("{field}={value}"))
+    /// Tell the metrics once `result` says the daily quota is used up
+    fn record_quota<T>(&self, result: &Result<T, NewsApiError>) {
+        if let Some(metrics) = &self.metrics {
+            if self.out_of_quota(result) {
+                metrics.record_quota_exhausted();
             }
         }
+    }
 
-        let mut url = base_url.to_owned();
+    /// Requests left today, if the client's metrics track a daily quota
+    pub(crate) fn quota_remaining(&self) -> Option<u32> {
+        self.metrics
+            .as_ref()
+            .and_then(|metrics| metrics.quota_remaining())
+    }
 
-        if params.is_empty() {
-            url
-        } else {
-            url.push('?');
-            url.push_str(&params.join("&"));
-            url
+    fn check_parameters(&self) -> Result<(), NewsApiError> {
+        if invalid_arguments_specified(&self.parameters) {
+            return Err(NewsApiError::InvalidParameterCombinationError);
         }
+        Ok(())
     }
 
-    fn invalid_arguments_specified(&self) -> bool {
-        (self.parameters.contains_key("country") || self.parameters.contains_key("category"))
-            && self.parameters.contains_key("sources")
+    fn remember(&self, url: &str, body: &[u8]) {
+        if let Some(stale) = &self.stale {
+            stale.store(url, body);
+        }
+    }
+
+    fn fall_back<T>(&self, error: NewsApiError, url: &str) -> Result<MaybeStale<T>, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        if !error.is_unavailable() {
+            return Err(error);
+        }
+
+        let stale = match &self.stale {
+            Some(stale) => stale,
+            None => return Err(error),
+        };
+
+        let cached = stale.lookup(url);
+        trace::stale_cache(url, cached.as_ref().map(|(_, age)| *age), &error);
+        if let Some(metrics) = &self.metrics {
+            metrics.record_stale_lookup(cached.is_some());
+        }
+
+        match cached {
+            Some((body, age)) => Ok(MaybeStale::stale(NewsAPIClient::decode(&body)?, age, error)),
+            None => Err(error),
+        }
     }
 
     fn handle_api_error(error_code: u16, error_string: String) -> NewsApiError {
@@ -156,40 +928,35 @@ impl NewsAPIClient {
         .to_owned()
     }
 
-    async fn fetch_resource_async(url: &str, api_key: &str) -> Result<String, NewsApiError> {
-        // TODO: create a client that can be reused
-        let client = reqwest::Client::builder()
-            .user_agent(NewsAPIClient::create_user_agent())
-            .build()?;
+    fn prepare_request(&self, url: &str, key: &ApiKey, timeout: Option<Duration>) -> HttpRequest {
+        let mut request =
+            HttpRequest::new(url).header("User-Agent", &NewsAPIClient::create_user_agent());
+        request.timeout = timeout;
+        self.auth_mode.apply(request, key)
+    }
 
-        let resp = client.get(url).header("X-Api-Key", api_key).send().await?;
+    /// Look at the `status` field of the body as well as the HTTP status, so an error body is
+    /// reported as an API error even if it arrives with a 2xx (as some proxies rewrite it).
+    fn handle_response(resp: HttpResponse) -> Result<HttpResponse, NewsApiError> {
+        if !resp.is_success() {
+            return Err(NewsAPIClient::handle_api_error(resp.status, resp.text()));
+        }
 
-        if resp.status().is_success() {
-            Ok(resp.text().await?)
-        } else {
-            Err(NewsAPIClient::handle_api_error(
-                resp.status().as_u16(),
-                resp.text().await?,
-            ))
+        match serde_json::from_slice::<StatusProbe>(&resp.body) {
+            Ok(StatusProbe {
+                status: Some(ResponseStatus::Error),
+            }) => Err(NewsAPIClient::body_error(&resp.body)),
+            _ => Ok(resp),
         }
     }
 
-    fn fetch_resource_sync(url: &str, api_key: &str) -> Result<String, NewsApiError> {
-        // TODO: create a client that can be reused
-        let client = reqwest::blocking::Client::builder()
-            .user_agent(NewsAPIClient::create_user_agent())
-            .build()?;
-
-        let resp = client.get(url).header("X-Api-Key", api_key).send()?;
-
-        if resp.status().is_success() {
-            Ok(resp.text()?)
-        } else {
-            Err(NewsAPIClient::handle_api_error(
-                resp.status().as_u16(),
-                resp.text()?,
-            ))
-        }
+    /// The error for a body whose `status` field says it is one, with the HTTP status NewsAPI
+    /// would have sent it with
+    fn body_error(body: &[u8]) -> NewsApiError {
+        let code = serde_json::from_slice::<ApiError>(body)
+            .map(|error| error.http_status())
+            .unwrap_or(500);
+        NewsAPIClient::handle_api_error(code, String::from_utf8_lossy(body).into_owned())
     }
 
     /// A date and optional time for the oldest article allowed
@@ -209,9 +976,8 @@ impl NewsAPIClient {
         operation: &str,
         dt_val: &DateTime<Utc>,
     ) -> &mut NewsAPIClient {
-        let dt_format = "%Y-%m-%dT%H:%M:%S";
         self.parameters
-            .insert(operation.to_owned(), dt_val.format(dt_format).to_string());
+            .insert(operation.to_owned(), encode_date(dt_val));
         self
     }
 
@@ -247,8 +1013,8 @@ impl NewsAPIClient {
 
     /// Defaults to all categories - see constants.rs
     pub fn category(&mut self, category: constants::Category) -> &mut NewsAPIClient {
-        let fmtd_category = format!("{category:?}").to_lowercase();
-        self.parameters.insert("category".to_owned(), fmtd_category);
+        self.parameters
+            .insert("category".to_owned(), category_code(category));
         self
     }
 
@@ -268,10 +1034,7 @@ impl NewsAPIClient {
     /// * Alternatively you can use the AND / OR / NOT keywords, and optionally group these with parenthesis.
     ///   e.g.: crypto AND (ethereum OR litecoin) NOT bitcoin
     pub fn query(&mut self, query: &str) -> &mut NewsAPIClient {
-        self.parameters.insert(
-            "q".to_owned(),
-            utf8_percent_encode(query, NON_ALPHANUMERIC).to_string(),
-        );
+        self.parameters.insert("q".to_owned(), encode_query(query));
         self
     }
 
@@ -298,16 +1061,133 @@ impl NewsAPIClient {
 
     pub fn sort_by(&mut self, sort_by: constants::SortMethod) -> &mut NewsAPIClient {
         self.parameters.insert(
-            "sort_by".to_owned(),
+            "sortBy".to_owned(),
             constants::SORT_METHOD_LOOKUP[sort_by].to_string(),
         );
         self
     }
 }
 
+/// The parameters of `spec`, encoded as the client's setters would
+fn spec_parameters(spec: &RequestSpec) -> HashMap<String, String> {
+    let mut parameters = HashMap::new();
+    let mut set = |name: &str, value: Option<String>| {
+        if let Some(value) = value {
+            parameters.insert(name.to_owned(), value);
+        }
+    };
+    let list = |values: &[String]| (!values.is_empty()).then(|| values.join(","));
+    let page_size = |size: Option<u32>| size.map(|size| size.to_string());
+
+    match spec {
+        RequestSpec::Everything(spec) => {
+            set("q", spec.query.as_deref().map(encode_query));
+            set("sources", list(&spec.sources));
+            set("domains", list(&spec.domains));
+            set("excludeDomains", list(&spec.exclude_domains));
+            set("from", spec.from.as_ref().map(encode_date));
+            set("to", spec.to.as_ref().map(encode_date));
+            set(
+                "language",
+                spec.language.map(|l| constants::LANG_LOOKUP[l].to_owned()),
+            );
+            set(
+                "sortBy",
+                spec.sort_by
+                    .map(|s| constants::SORT_METHOD_LOOKUP[s].to_owned()),
+            );
+            set("pageSize", page_size(spec.page_size));
+            set("page", spec.page.map(|page| page.to_string()));
+        }
+        RequestSpec::TopHeadlines(spec) => {
+            set("q", spec.query.as_deref().map(encode_query));
+            set(
+                "country",
+                spec.country
+                    .map(|c| constants::COUNTRY_LOOKUP[c].to_owned()),
+            );
+            set("category", spec.category.map(category_code));
+            set("sources", list(&spec.sources));
+            set("pageSize", page_size(spec.page_size));
+            set("page", spec.page.map(|page| page.to_string()));
+        }
+        RequestSpec::Sources(spec) => {
+            set("category", spec.category.map(category_code));
+            set(
+                "language",
+                spec.language.map(|l| constants::LANG_LOOKUP[l].to_owned()),
+            );
+            set(
+                "country",
+                spec.country
+                    .map(|c| constants::COUNTRY_LOOKUP[c].to_owned()),
+            );
+        }
+    }
+    parameters
+}
+
+fn encode_query(query: &str) -> String {
+    utf8_percent_encode(query, NON_ALPHANUMERIC).to_string()
+}
+
+fn encode_date(date: &DateTime<Utc>) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+fn category_code(category: constants::Category) -> String {
+    format!("{category:?}").to_lowercase()
+}
+
+fn build_url(base_url: &str, parameters: &HashMap<String, String>, allowed: &[&str]) -> String {
+    let params: Vec<String> = allowed
+        .iter()
+        .filter_map(|field| {
+            parameters
+                .get(*field)
+                .map(|value| format!("{field}={value}"))
+        })
+        .collect();
+
+    if params.is_empty() {
+        base_url.to_owned()
+    } else {
+        format!("{}?{}", base_url, params.join("&"))
+    }
+}
+
+fn invalid_arguments_specified(parameters: &HashMap<String, String>) -> bool {
+    (parameters.contains_key("country") || parameters.contains_key("category"))
+        && parameters.contains_key("sources")
+}
+
+/// Reads through `reader`, keeping a copy of everything read in `copy` if there is one
+#[cfg(feature = "blocking")]
+struct Tee<'a, R> {
+    reader: R,
+    /// Where to copy what is read, until it is set to `None`
+    copy: &'a RefCell<Option<Vec<u8>>>,
+}
+
+#[cfg(feature = "blocking")]
+impl<R: Read> Read for Tee<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        if let Some(copy) = self.copy.borrow_mut().as_mut() {
+            copy.extend_from_slice(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    use crate::{
+        payload::source::Sources,
+        transport::{MockTransport, Route},
+    };
 
     #[test]
     fn handle_api_error() {
@@ -340,7 +1220,7 @@ mod tests {
         api.country(constants::Country::UnitedStatesofAmerica);
         let expected = "https://newsapi.org/v2/sources?language=en&country=us".to_owned();
         let allowed_params = vec!["category", "language", "country"];
-        let url = api.build_url(constants::SOURCES_URL, allowed_params);
+        let url = super::build_url(constants::SOURCES_URL, &api.parameters, &allowed_params);
         assert_eq!(expected, url);
     }
 
@@ -411,7 +1291,8 @@ mod tests {
     #[test]
     fn new() {
         let api = NewsAPIClient::new("123".to_string());
-        assert_eq!(api.api_key, "123".to_string());
+        assert_eq!(api.api_key.expose(), "123");
+        assert!(!format!("{api:?}").contains("123"));
     }
 
     #[test]
@@ -421,6 +1302,769 @@ mod tests {
         assert_eq!(api.parameters.get("page"), Some(&"20".to_owned()));
     }
 
+    #[test]
+    fn fall_back() {
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.stale_while_error(Duration::from_secs(60));
+        let url = api.sources().url();
+        let unavailable = || NewsAPIClient::handle_api_error(429, "apiKeyExhausted".into());
+
+        let missing = api.fall_back::<serde_json::Value>(unavailable(), &url);
+        assert!(missing.is_err());
+
+        api.remember(&url, br#"{"status": "ok", "sources": []}"#);
+
+        let served = api
+            .fall_back::<serde_json::Value>(unavailable(), &url)
+            .unwrap();
+        assert!(served.is_stale());
+        assert_eq!(served.value["status"], "ok");
+
+        let bad_request = NewsAPIClient::handle_api_error(400, "parametersMissing".into());
+        assert!(api
+            .fall_back::<serde_json::Value>(bad_request, &url)
+            .is_err());
+    }
+
+    /// Send with every enabled flavour so a test covers the sync and async paths alike
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn send_each_flavour<E: Endpoint>(
+        request: &Request<'_, E>,
+    ) -> Vec<Result<E::Payload, NewsApiError>> {
+        each_flavour!(request.send_async(), request.send_sync())
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn send_or_stale_each_flavour<E: Endpoint>(
+        request: &Request<'_, E>,
+    ) -> Vec<Result<MaybeStale<E::Payload>, NewsApiError>> {
+        each_flavour!(request.send_async_or_stale(), request.send_sync_or_stale())
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn mock_sources() -> Arc<MockTransport> {
+        let body = include_str!("../resources/example_sources.json");
+        Arc::new(MockTransport::new().respond(
+            Route::new(constants::SOURCES_URL).param("language", "en"),
+            HttpResponse::new(200, body),
+        ))
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn send_sync_with_transport() {
+        let mock = mock_sources();
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock.clone())
+            .language(constants::Language::English);
+
+        let sources = api.sources().send_sync().unwrap();
+        assert_eq!(sources.sources.len(), 4);
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].header_value("X-Api-Key"), Some("123"));
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn send_async_with_transport() {
+        let mock = mock_sources();
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock.clone())
+            .language(constants::Language::English);
+
+        let sources = api.sources().send_async().await.unwrap();
+        assert_eq!(sources.sources.len(), 4);
+
+        api.language(constants::Language::German);
+        let unmatched = api.sources().send_async().await;
+        assert!(matches!(
+            unmatched,
+            Err(NewsApiError::TransportError { .. })
+        ));
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn send_api_error() {
+        let mock = Arc::new(MockTransport::new().respond(
+            Route::new(constants::SOURCES_URL),
+            HttpResponse::new(401, "apiKeyInvalid"),
+        ));
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock.clone());
+
+        for result in send_each_flavour(&api.sources()) {
+            let error = result.unwrap_err();
+            assert_eq!(error.to_string(), "Unauthorized: 401 => apiKeyInvalid");
+        }
+        assert!(!mock.requests().is_empty());
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn send_validates_before_io() {
+        let mock = Arc::new(MockTransport::new());
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock.clone());
+
+        api.with_sources("bbc-news".to_owned())
+            .country(constants::Country::Germany);
+
+        for result in send_each_flavour(&api.top_headlines()) {
+            assert!(matches!(
+                result,
+                Err(NewsApiError::InvalidParameterCombinationError)
+            ));
+        }
+        assert!(mock.requests().is_empty());
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn send_or_stale() {
+        let body = include_str!("../resources/example_sources.json");
+        let healthy = Arc::new(MockTransport::new().respond(
+            Route::new(constants::SOURCES_URL),
+            HttpResponse::new(200, body),
+        ));
+        let exhausted = Arc::new(MockTransport::new().respond(
+            Route::new(constants::SOURCES_URL),
+            HttpResponse::new(429, "apiKeyExhausted"),
+        ));
+
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(exhausted.clone());
+        for result in send_or_stale_each_flavour(&api.sources()) {
+            assert!(matches!(result, Err(NewsApiError::TooManyRequests { .. })));
+        }
+
+        api.stale_while_error(Duration::from_secs(60))
+            .transport(healthy);
+        for result in send_or_stale_each_flavour(&api.sources()) {
+            assert!(!result.unwrap().is_stale());
+        }
+
+        api.transport(exhausted);
+        for result in send_or_stale_each_flavour(&api.sources()) {
+            let served = result.unwrap();
+            assert_eq!(served.value.sources.len(), 4);
+            let staleness = served.staleness.unwrap();
+            assert!(matches!(
+                staleness.error,
+                NewsApiError::TooManyRequests { .. }
+            ));
+        }
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn for_each_article_sync_feeds_stale_cache() {
+        let body = include_str!("../resources/example_everything.json");
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.stale_while_error(Duration::from_secs(60))
+            .transport(Arc::new(MockTransport::new().respond(
+                Route::new(constants::EVERYTHING_URL),
+                HttpResponse::new(200, body),
+            )));
+        let summary = api.everything().for_each_article_sync(|_| ()).unwrap();
+        assert_eq!(summary.count, 2);
+
+        api.transport(Arc::new(MockTransport::new().respond(
+            Route::new(constants::EVERYTHING_URL),
+            HttpResponse::new(503, "down"),
+        )));
+        let served = api.everything().send_sync_or_stale().unwrap();
+        assert!(served.is_stale());
+        assert_eq!(served.value.articles.len(), 2);
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn send_fixture(status: u16, body: &str) -> Vec<Result<Sources, NewsApiError>> {
+        let mock = Arc::new(MockTransport::new().respond(
+            Route::new(constants::SOURCES_URL),
+            HttpResponse::new(status, body),
+        ));
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock);
+        send_each_flavour(&api.sources())
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn error_bodies() {
+        let invalid_key = include_str!("../resources/example_error_api_key_invalid.json");
+        let rate_limited = include_str!("../resources/example_error_rate_limited.json");
+        let unexpected = include_str!("../resources/example_error_unexpected.json");
+
+        for status in [200, 401] {
+            for result in send_fixture(status, invalid_key) {
+                let error = result.unwrap_err();
+                assert!(matches!(
+                    error,
+                    NewsApiError::Unauthorized { code: 401, .. }
+                ));
+                assert_eq!(error.api_error().unwrap().code, "apiKeyInvalid");
+            }
+        }
+
+        for status in [200, 429] {
+            for result in send_fixture(status, rate_limited) {
+                let error = result.unwrap_err();
+                assert!(matches!(
+                    error,
+                    NewsApiError::TooManyRequests { code: 429, .. }
+                ));
+                assert_eq!(error.api_error().unwrap().code, "rateLimited");
+            }
+        }
+
+        for result in send_fixture(200, unexpected) {
+            assert!(matches!(
+                result,
+                Err(NewsApiError::ServerError { code: 500, .. })
+            ));
+        }
+
+        for result in send_fixture(200, r#"{"status": "error"}"#) {
+            let error = result.unwrap_err();
+            assert!(matches!(error, NewsApiError::ServerError { code: 500, .. }));
+            assert!(error.api_error().is_none());
+        }
+
+        let sources = include_str!("../resources/example_sources.json");
+        for result in send_fixture(200, sources) {
+            assert_eq!(result.unwrap().sources.len(), 4);
+        }
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn middleware() {
+        let mock = mock_sources();
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock.clone())
+            .before_request(|request| {
+                request.url.push_str("&language=en");
+                request
+                    .headers
+                    .push(("X-Signature".into(), "signed".into()));
+                Ok(Flow::Continue)
+            })
+            .after_response(|request, response| {
+                assert_eq!(request.header_value("x-signature"), Some("signed"));
+                Ok(response.header("X-Audited", "yes"))
+            })
+            .after_response(|_, response| {
+                assert_eq!(response.header_value("x-audited"), Some("yes"));
+                Ok(response)
+            })
+            .country(constants::Country::Germany);
+
+        for result in send_each_flavour(&api.sources()) {
+            assert_eq!(result.unwrap().sources.len(), 4);
+        }
+
+        let requests = mock.requests();
+        assert!(requests.iter().all(|request| request.url
+            == "https://newsapi.org/v2/sources?country=de&language=en"
+            && request.header_value("X-Signature") == Some("signed")));
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn middleware_short_circuit() {
+        let mock = Arc::new(MockTransport::new());
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock.clone()).before_request(|_| {
+            let body = include_str!("../resources/example_sources.json");
+            Ok(Flow::Respond(HttpResponse::new(200, body)))
+        });
+
+        for result in send_each_flavour(&api.sources()) {
+            assert_eq!(result.unwrap().sources.len(), 4);
+        }
+        assert!(mock.requests().is_empty());
+
+        api.before_request(|_| {
+            Err(NewsApiError::TransportError {
+                message: "unreachable".into(),
+            })
+        })
+        .after_response(|_, _| Ok(HttpResponse::new(500, "replaced")));
+        for result in send_each_flavour(&api.sources()) {
+            assert!(matches!(result, Err(NewsApiError::ServerError { .. })));
+        }
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn metrics() {
+        let rate_limited = include_str!("../resources/example_error_rate_limited.json");
+        let exhausted = Arc::new(MockTransport::new().respond(
+            Route::new(constants::SOURCES_URL),
+            HttpResponse::new(429, rate_limited),
+        ));
+
+        let metrics = Arc::new(Metrics::with_daily_quota(10));
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.metrics(metrics.clone())
+            .stale_while_error(Duration::from_secs(60))
+            .transport(mock_sources())
+            .language(constants::Language::English);
+        let flavours = send_or_stale_each_flavour(&api.sources()).len() as u64;
+
+        api.transport(exhausted);
+        send_or_stale_each_flavour(&api.sources());
+        api.country(constants::Country::Germany);
+        send_or_stale_each_flavour(&api.sources());
+
+        assert_eq!(metrics.requests("sources", "ok"), flavours);
+        assert_eq!(metrics.requests("sources", "error"), 2 * flavours);
+        assert_eq!(metrics.errors("TooManyRequests"), 2 * flavours);
+        assert_eq!(metrics.stale_cache_hit_ratio(), Some(0.5));
+        assert_eq!(metrics.quota_remaining(), Some(10 - 3 * flavours as u32));
+        assert!(metrics
+            .prometheus()
+            .contains("newsapi_requests_total{endpoint=\"sources\",outcome=\"ok\"}"));
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn auth_mode() {
+        let mut api = NewsAPIClient::new("s3cret".to_owned());
+        api.language(constants::Language::English);
+
+        let mock = mock_sources();
+        api.transport(mock.clone()).auth_mode(AuthMode::Bearer);
+        for result in send_each_flavour(&api.sources()) {
+            result.unwrap();
+        }
+        let request = &mock.requests()[0];
+        assert_eq!(request.header_value("Authorization"), Some("Bearer s3cret"));
+        assert_eq!(request.header_value("X-Api-Key"), None);
+
+        let mock = mock_sources();
+        api.transport(mock.clone())
+            .auth_mode(AuthMode::QueryParameter)
+            .stale_while_error(Duration::from_secs(60));
+        for result in send_each_flavour(&api.sources()) {
+            result.unwrap();
+        }
+        let request = &mock.requests()[0];
+        assert_eq!(
+            request.url,
+            "https://newsapi.org/v2/sources?language=en&apiKey=s3cret"
+        );
+        assert_eq!(request.header_value("X-Api-Key"), None);
+        assert!(!format!("{request:?}").contains("s3cret"));
+
+        // the stale cache is keyed on the URL without the key
+        let url = api.sources().url();
+        assert!(api.stale.as_ref().unwrap().lookup(&url).is_some());
+
+        api.transport(Arc::new(MockTransport::new()));
+        for result in send_each_flavour(&api.sources()) {
+            let error = result.unwrap_err().to_string();
+            assert!(error.contains("apiKey=REDACTED"), "{}", error);
+            assert!(!error.contains("s3cret"));
+        }
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn timeouts_and_deadlines() {
+        let mock = mock_sources();
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock.clone())
+            .language(constants::Language::English);
+
+        api.timeout(Duration::from_secs(5));
+        for result in send_each_flavour(&api.sources()) {
+            result.unwrap();
+        }
+        api.deadline(Duration::from_secs(1));
+        for result in send_each_flavour(&api.sources()) {
+            result.unwrap();
+        }
+
+        let requests = mock.requests();
+        let flavours = requests.len() / 2;
+        assert_eq!(requests[0].timeout, Some(Duration::from_secs(5)));
+        // the remaining deadline caps the per-request timeout
+        assert!(requests[flavours].timeout.unwrap() <= Duration::from_secs(1));
+
+        api.deadline(Duration::ZERO);
+        for result in send_each_flavour(&api.sources()) {
+            assert!(matches!(result, Err(NewsApiError::Timeout)));
+        }
+        assert_eq!(mock.requests().len(), requests.len());
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn deadline_covers_failover() {
+        let rate_limited = include_str!("../resources/example_error_rate_limited.json");
+        let pool = Arc::new(KeyPool::new().key("slow", "key-a").key("spare", "key-b"));
+
+        let mut api = NewsAPIClient::new("unused".to_owned());
+        api.transport(mock_sources())
+            .key_pool(pool.clone())
+            .deadline(Duration::from_millis(20))
+            .after_response(
+                move |request, response| match request.header_value("X-Api-Key") {
+                    Some("key-a") => {
+                        std::thread::sleep(Duration::from_millis(30));
+                        Ok(HttpResponse::new(429, rate_limited))
+                    }
+                    _ => Ok(response),
+                },
+            )
+            .language(constants::Language::English);
+
+        // the spare key is not tried once the deadline has passed; by the next send the slow
+        // key is benched and the spare one is used straight away
+        let results = send_each_flavour(&api.sources());
+        assert!(matches!(results[0], Err(NewsApiError::Timeout)));
+        assert_eq!(pool.usage()[1].requests, results.len() as u64 - 1);
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn cancelled() {
+        let mock = mock_sources();
+        let token = CancellationToken::new();
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock.clone())
+            .cancel_with(token.clone())
+            .language(constants::Language::English);
+
+        token.cancel();
+        for result in send_each_flavour(&api.sources()) {
+            assert!(matches!(result, Err(NewsApiError::Cancelled)));
+        }
+        assert!(mock.requests().is_empty());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn cancel_in_flight() {
+        /// Never answers
+        #[derive(Debug)]
+        struct Hang;
+
+        #[async_trait::async_trait]
+        impl AsyncTransport for Hang {
+            async fn execute(&self, _: HttpRequest) -> Result<HttpResponse, NewsApiError> {
+                std::future::pending().await
+            }
+        }
+
+        let token = CancellationToken::new();
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.cancel_with(token.clone());
+        api.transport = Arc::new(Hang);
+
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(10));
+            token.cancel();
+        });
+        let result = api.sources().send_async().await;
+        assert!(matches!(result, Err(NewsApiError::Cancelled)));
+        canceller.join().unwrap();
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn single_flight() {
+        /// Counts its calls and takes a few polls to answer
+        #[derive(Debug, Default)]
+        struct Slow(AtomicUsize);
+
+        #[async_trait::async_trait]
+        impl AsyncTransport for Slow {
+            async fn execute(&self, _: HttpRequest) -> Result<HttpResponse, NewsApiError> {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                for _ in 0..3 {
+                    tokio::task::yield_now().await;
+                }
+                Ok(HttpResponse::new(
+                    200,
+                    include_str!("../resources/example_sources.json"),
+                ))
+            }
+        }
+
+        let transport = Arc::new(Slow::default());
+        let flight = Arc::new(SingleFlight::new());
+        let metrics = Arc::new(Metrics::new());
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.single_flight(flight.clone()).metrics(metrics.clone());
+        api.transport = transport.clone();
+
+        let request = api.sources();
+        let (a, b, c) = tokio::join!(
+            request.send_async(),
+            request.send_async(),
+            request.send_async()
+        );
+        for result in [a, b, c] {
+            assert_eq!(result.unwrap().sources.len(), 4);
+        }
+        assert_eq!(transport.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(flight.saved(), 2);
+        assert_eq!(metrics.coalesced_requests(), 2);
+        assert_eq!(metrics.requests("sources", "ok"), 1);
+
+        // nothing is shared once the call is over
+        request.send_async().await.unwrap();
+        assert_eq!(transport.0.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(flight.saved(), 2);
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn circuit_breaker() {
+        let down = Arc::new(MockTransport::new().respond(
+            Route::new(constants::SOURCES_URL),
+            HttpResponse::new(503, "Service Unavailable"),
+        ));
+        let breaker = Arc::new(
+            CircuitBreaker::new()
+                .window(1)
+                .minimum_requests(1)
+                .open_for(Duration::from_secs(60)),
+        );
+
+        let mut api = NewsAPIClient::new("123".to_owned());
+        assert_eq!(api.circuit_state(), None);
+        api.circuit_breaker(breaker)
+            .stale_while_error(Duration::from_secs(60))
+            .transport(mock_sources())
+            .language(constants::Language::English);
+        send_each_flavour(&api.sources());
+        assert_eq!(api.circuit_state(), Some(CircuitState::Closed));
+
+        api.transport(down.clone());
+        let results = send_or_stale_each_flavour(&api.sources());
+        assert_eq!(api.circuit_state(), Some(CircuitState::Open));
+        assert_eq!(down.requests().len(), 1);
+
+        // once open NewsAPI is not called; the stale cache answers instead
+        for result in results {
+            let served = result.unwrap();
+            assert_eq!(served.value.sources.len(), 4);
+        }
+        for result in send_each_flavour(&api.sources()) {
+            assert!(matches!(result, Err(NewsApiError::CircuitOpen)));
+        }
+        assert_eq!(down.requests().len(), 1);
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn key_pool() {
+        let rate_limited = include_str!("../resources/example_error_rate_limited.json");
+        let pool = Arc::new(
+            KeyPool::new()
+                .key("limited", "key-a")
+                .key("healthy", "key-b"),
+        );
+
+        let mut api = NewsAPIClient::new("unused".to_owned());
+        api.transport(mock_sources())
+            .key_pool(pool.clone())
+            .after_response(
+                move |request, response| match request.header_value("X-Api-Key") {
+                    Some("key-a") => Ok(HttpResponse::new(429, rate_limited)),
+                    _ => Ok(response),
+                },
+            )
+            .language(constants::Language::English);
+
+        let results = send_each_flavour(&api.sources());
+        for result in &results {
+            assert_eq!(result.as_ref().unwrap().sources.len(), 4);
+        }
+
+        // the limited key is benched after the first failure and not tried again
+        let usage = pool.usage();
+        assert_eq!(usage[0].requests, 1);
+        assert_eq!(usage[0].failures, 1);
+        assert!(usage[0].benched_for.is_some());
+        assert_eq!(usage[1].requests, results.len() as u64);
+
+        let pool = Arc::new(KeyPool::new().key("only", "key-a"));
+        api.key_pool(pool.clone());
+        for result in send_each_flavour(&api.sources()) {
+            assert!(result.is_err());
+        }
+        assert_eq!(pool.usage()[0].requests, 1);
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn key_pool_quota() {
+        let exhausted = r#"{"status": "error", "code": "apiKeyExhausted", "message": "done"}"#;
+        let pool = Arc::new(KeyPool::new().key("spent", "key-a").key("fresh", "key-b"));
+        let metrics = Arc::new(Metrics::with_daily_quota(100));
+
+        let mut api = NewsAPIClient::new("unused".to_owned());
+        api.transport(mock_sources())
+            .key_pool(pool)
+            .metrics(metrics.clone())
+            .after_response(
+                move |request, response| match request.header_value("X-Api-Key") {
+                    Some("key-a") => Ok(HttpResponse::new(429, exhausted)),
+                    _ => Ok(response),
+                },
+            )
+            .language(constants::Language::English);
+
+        // the spent key fails over to the fresh one, so the quota is not used up
+        for result in send_each_flavour(&api.sources()) {
+            result.unwrap();
+        }
+        assert_ne!(metrics.quota_remaining(), Some(0));
+
+        api.key_pool(Arc::new(KeyPool::new().key("spent", "key-a")));
+        for result in send_each_flavour(&api.sources()) {
+            assert!(result.is_err());
+        }
+        assert_eq!(metrics.quota_remaining(), Some(0));
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn key_pool_keeps_upstream_error() {
+        let exhausted = r#"{"status": "error", "code": "apiKeyExhausted", "message": "done"}"#;
+        let spent = NewsAPIClient::handle_api_error(429, exhausted.to_owned());
+        let metrics = Arc::new(Metrics::with_daily_quota(100));
+
+        let pool = Arc::new(KeyPool::new().key("spent", "key-a").key("spare", "key-b"));
+        pool.fail_over(1, &spent);
+
+        let mut api = NewsAPIClient::new("unused".to_owned());
+        api.transport(mock_sources())
+            .key_pool(pool)
+            .metrics(metrics.clone())
+            .after_response(move |_, _| Ok(HttpResponse::new(429, exhausted)))
+            .language(constants::Language::English);
+
+        // the spare key was used up before, so once the first key fails there is nothing left
+        // to fail over to and NewsAPI's answer is what the caller sees
+        let results = send_each_flavour(&api.sources());
+        assert!(matches!(
+            results[0],
+            Err(NewsApiError::TooManyRequests { .. })
+        ));
+        assert_eq!(metrics.quota_remaining(), Some(0));
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn key_pool_behind_open_circuit() {
+        let breaker = Arc::new(
+            CircuitBreaker::new()
+                .window(1)
+                .minimum_requests(1)
+                .open_for(Duration::from_secs(60)),
+        );
+        let pool = Arc::new(KeyPool::new().key("a", "key-a").key("b", "key-b"));
+
+        let mut api = NewsAPIClient::new("unused".to_owned());
+        api.transport(mock_sources())
+            .circuit_breaker(breaker)
+            .key_pool(pool.clone())
+            .after_response(|_, _| Ok(HttpResponse::new(503, "Service Unavailable")))
+            .language(constants::Language::English);
+
+        // a request the circuit refuses does not count against any key
+        let results = send_each_flavour(&api.sources());
+        assert_eq!(api.circuit_state(), Some(CircuitState::Open));
+        for result in &results[1..] {
+            assert!(matches!(result, Err(NewsApiError::CircuitOpen)));
+        }
+        let requests: u64 = pool.usage().iter().map(|usage| usage.requests).sum();
+        assert_eq!(requests, 1);
+    }
+
+    #[cfg(all(feature = "tracing", any(feature = "async", feature = "blocking")))]
+    #[test]
+    fn tracing() {
+        use crate::trace::capture::Capture;
+
+        let rate_limited = include_str!("../resources/example_error_rate_limited.json");
+        let healthy = mock_sources();
+        let exhausted = Arc::new(MockTransport::new().respond(
+            Route::new(constants::SOURCES_URL),
+            HttpResponse::new(429, rate_limited),
+        ));
+
+        let capture = Arc::new(Capture::default());
+        tracing::subscriber::with_default(capture.clone(), || {
+            let mut api = NewsAPIClient::new("123".to_owned());
+            api.stale_while_error(Duration::from_secs(60))
+                .transport(healthy)
+                .language(constants::Language::English);
+            send_or_stale_each_flavour(&api.sources());
+
+            api.transport(exhausted);
+            for result in send_or_stale_each_flavour(&api.sources()) {
+                assert!(result.unwrap().is_stale());
+            }
+        });
+
+        let spans = capture.spans();
+        let flavours = spans.len() / 2;
+        assert!(flavours > 0);
+        for (i, span) in spans.iter().enumerate() {
+            assert_eq!(span.name, "newsapi.request");
+            assert_eq!(span.fields["endpoint"], "sources");
+            assert_eq!(span.fields["parameters"], "language=en");
+            assert_eq!(span.fields["page"], "1");
+            assert_eq!(span.fields["attempt"], "1");
+            assert!(span.fields.contains_key("latency_ms"));
+
+            if i < flavours {
+                assert_eq!(span.fields["status"], "200");
+                assert!(!span.fields.contains_key("error"));
+            } else {
+                assert_eq!(span.fields["status"], "429");
+                assert!(span.fields["error"].starts_with("TooManyRequests"));
+            }
+        }
+
+        let events = capture.events();
+        let messages = |message: &str| {
+            events
+                .iter()
+                .filter(|event| event.fields["message"] == message)
+                .collect::<Vec<_>>()
+        };
+
+        let refused = messages("newsapi.quota.refused");
+        assert_eq!(refused.len(), flavours);
+        assert_eq!(refused[0].fields["code"], "rateLimited");
+        assert_eq!(refused[0].span, Some("newsapi.request"));
+
+        let hits = messages("newsapi.stale_cache.hit");
+        assert_eq!(hits.len(), flavours);
+        assert_eq!(hits[0].fields["parameters"], "language=en");
+        assert!(hits[0].fields.contains_key("age_ms"));
+        assert_eq!(hits[0].span, None);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn send_async_is_send() {
+        fn assert_send<T: Send>(_: T) {}
+        let api = NewsAPIClient::new("123".to_owned());
+        assert_send(api.sources().send_async());
+        assert_send(api.sources().send_async_or_stale());
+    }
+
     #[test]
     fn page_size() {
         let mut api = NewsAPIClient::new("123".to_owned());
@@ -430,4 +2074,74 @@ mod tests {
         api.page_size(400);
         assert_eq!(api.parameters.get("pageSize"), Some(&"30".to_owned()));
     }
+
+    #[test]
+    fn explain() {
+        let mut api = NewsAPIClient::new("secret".to_owned());
+        api.timeout(Duration::from_millis(2500))
+            .country(constants::Country::Germany)
+            .language(constants::Language::English);
+        let explanation = api.everything().explain();
+        let user_agent = NewsAPIClient::create_user_agent();
+
+        assert_eq!(
+            explanation.url,
+            "https://newsapi.org/v2/everything?language=en"
+        );
+        assert_eq!(
+            explanation.headers,
+            vec![
+                ("User-Agent".to_owned(), user_agent.clone()),
+                ("X-Api-Key".to_owned(), "REDACTED".to_owned()),
+            ]
+        );
+        assert_eq!(
+            explanation.warnings,
+            vec![
+                "country is not sent, everything does not take it".to_owned(),
+                "everything needs one of q, sources, domains, NewsAPI will answer parametersMissing"
+                    .to_owned(),
+            ]
+        );
+        assert_eq!(explanation.http_requests, 1);
+        assert_eq!(
+            explanation.curl,
+            format!(
+                "curl --max-time 2.5 -H 'User-Agent: {user_agent}' -H 'X-Api-Key: '\"$NEWSAPI_KEY\" \
+                 'https://newsapi.org/v2/everything?language=en'"
+            )
+        );
+        assert!(!explanation.to_string().contains("secret"));
+
+        api.auth_mode(AuthMode::QueryParameter)
+            .with_sources("it's-news".to_owned())
+            .key_pool(Arc::new(KeyPool::new().key("a", "1").key("b", "2")));
+        let explanation = api.top_headlines().explain();
+        assert_eq!(
+            explanation.url,
+            "https://newsapi.org/v2/top-headlines?country=de&sources=it's-news&apiKey=REDACTED"
+        );
+        assert!(explanation.curl.ends_with(
+            "'https://newsapi.org/v2/top-headlines?country=de&sources=it'\\''s-news&apiKey='\"$NEWSAPI_KEY\""
+        ));
+        assert_eq!(
+            explanation.warnings,
+            vec![
+                "language is not sent, top-headlines does not take it".to_owned(),
+                NewsApiError::InvalidParameterCombinationError.to_string(),
+            ]
+        );
+        // the send would fail before calling NewsAPI
+        assert_eq!(explanation.http_requests, 0);
+        assert_eq!(explanation.max_http_requests, 0);
+
+        let mut pooled = NewsAPIClient::new("secret".to_owned());
+        pooled
+            .query("rust")
+            .key_pool(Arc::new(KeyPool::new().key("a", "1").key("b", "2")));
+        let explanation = pooled.everything().explain();
+        assert!(explanation.warnings.is_empty());
+        assert_eq!(explanation.http_requests, 1);
+        assert_eq!(explanation.max_http_requests, 2);
+    }
 }
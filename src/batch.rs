@@ -0,0 +1,455 @@
+use super::api::NewsAPIClient;
+use super::error::NewsApiError;
+use super::explain::Explanation;
+use super::payload::{article::Articles, source::Sources};
+use super::spec::RequestSpec;
+#[cfg(feature = "async")]
+use std::future::{poll_fn, Future};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "blocking")]
+use std::sync::{atomic::AtomicBool, Mutex};
+#[cfg(feature = "async")]
+use std::task::Poll;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::time::Instant;
+
+/// Many independent requests sent through one client, a few at a time. Everything the client
+/// shares between sends (key pool, circuit breaker, metrics, middleware) applies across the
+/// batch. Once the daily quota is used up, requests not yet started fail with
+/// `NewsApiError::QuotaExhausted` instead of being sent. The client has no rate limiter, so
+/// `concurrency` is the only limit on how fast requests go out.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    specs: Vec<RequestSpec>,
+    concurrency: usize,
+}
+
+/// The response to one request of a `Batch`, decoded for the endpoint its spec is for
+#[derive(Debug)]
+pub enum BatchPayload {
+    Articles(Articles),
+    Sources(Sources),
+}
+
+/// The result of every request of a `Batch`, in the order they were added
+#[derive(Debug)]
+pub struct BatchResults {
+    pub results: Vec<Result<BatchPayload, NewsApiError>>,
+    /// HTTP requests that went out to NewsAPI, counting attempts with other keys of a key pool
+    pub requests_sent: usize,
+}
+
+impl Default for Batch {
+    fn default() -> Batch {
+        Batch::new()
+    }
+}
+
+impl Batch {
+    /// An empty batch sending four requests at a time
+    pub fn new() -> Batch {
+        Batch {
+            specs: vec![],
+            concurrency: 4,
+        }
+    }
+
+    pub fn request(mut self, spec: RequestSpec) -> Batch {
+        self.specs.push(spec);
+        self
+    }
+
+    pub fn requests<I>(mut self, specs: I) -> Batch
+    where
+        I: IntoIterator<Item = RequestSpec>,
+    {
+        self.specs.extend(specs);
+        self
+    }
+
+    /// How many requests may be in flight at once
+    pub fn concurrency(mut self, limit: usize) -> Batch {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.specs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    /// Describe what sending the batch through `client` would do, one `Explanation` per
+    /// request in the order they were added. Requests the daily quota would stop part way
+    /// through are still counted.
+    pub fn explain(&self, client: &NewsAPIClient) -> Vec<Explanation> {
+        self.specs
+            .iter()
+            .map(|spec| client.explain_spec(spec))
+            .collect()
+    }
+
+    /// Send every request through `client`
+    #[cfg(feature = "async")]
+    pub async fn send_async(&self, client: &NewsAPIClient) -> BatchResults {
+        type InFlight<'a> =
+            Pin<Box<dyn Future<Output = Result<BatchPayload, NewsApiError>> + Send + 'a>>;
+
+        let sent = AtomicUsize::new(0);
+        let mut results: Vec<Option<Result<BatchPayload, NewsApiError>>> =
+            self.specs.iter().map(|_| None).collect();
+        let mut next = 0;
+        let mut exhausted = false;
+        let mut in_flight: Vec<(usize, InFlight<'_>)> = vec![];
+
+        poll_fn(|cx| loop {
+            while in_flight.len() < self.concurrency && next < self.specs.len() {
+                if exhausted || client.quota_spent() {
+                    exhausted = true;
+                    results[next] = Some(Err(NewsApiError::QuotaExhausted));
+                } else {
+                    let send = BatchPayload::send_async(client, &self.specs[next], &sent);
+                    in_flight.push((next, Box::pin(send)));
+                }
+                next += 1;
+            }
+
+            let mut finished = false;
+            in_flight.retain_mut(|(index, send)| match send.as_mut().poll(cx) {
+                Poll::Ready(result) => {
+                    exhausted |= uses_up_quota(client, &result);
+                    results[*index] = Some(result);
+                    finished = true;
+                    false
+                }
+                Poll::Pending => true,
+            });
+
+            if in_flight.is_empty() && next == self.specs.len() {
+                return Poll::Ready(());
+            }
+            if !finished {
+                return Poll::Pending;
+            }
+        })
+        .await;
+
+        BatchResults::new(results, sent.load(Ordering::SeqCst))
+    }
+
+    /// Send every request through `client` from a pool of `concurrency` threads
+    #[cfg(feature = "blocking")]
+    pub fn send_sync(&self, client: &NewsAPIClient) -> BatchResults {
+        let sent = AtomicUsize::new(0);
+        let next = AtomicUsize::new(0);
+        let exhausted = AtomicBool::new(false);
+        let results: Mutex<Vec<Option<Result<BatchPayload, NewsApiError>>>> =
+            Mutex::new(self.specs.iter().map(|_| None).collect());
+
+        std::thread::scope(|scope| {
+            for _ in 0..self.concurrency.min(self.specs.len()) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let spec = match self.specs.get(index) {
+                        Some(spec) => spec,
+                        None => return,
+                    };
+                    let result = if exhausted.load(Ordering::SeqCst) || client.quota_spent() {
+                        exhausted.store(true, Ordering::SeqCst);
+                        Err(NewsApiError::QuotaExhausted)
+                    } else {
+                        BatchPayload::send_sync(client, spec, &sent)
+                    };
+                    if uses_up_quota(client, &result) {
+                        exhausted.store(true, Ordering::SeqCst);
+                    }
+                    results.lock().unwrap()[index] = Some(result);
+                });
+            }
+        });
+
+        BatchResults::new(results.into_inner().unwrap(), sent.into_inner())
+    }
+}
+
+impl BatchPayload {
+    pub fn articles(&self) -> Option<&Articles> {
+        match self {
+            BatchPayload::Articles(articles) => Some(articles),
+            BatchPayload::Sources(_) => None,
+        }
+    }
+
+    pub fn sources(&self) -> Option<&Sources> {
+        match self {
+            BatchPayload::Sources(sources) => Some(sources),
+            BatchPayload::Articles(_) => None,
+        }
+    }
+
+    #[cfg(feature = "async")]
+    async fn send_async(
+        client: &NewsAPIClient,
+        spec: &RequestSpec,
+        sent: &AtomicUsize,
+    ) -> Result<BatchPayload, NewsApiError> {
+        match spec {
+            RequestSpec::Sources(_) => client
+                .send_spec_async(spec, sent, Instant::now())
+                .await
+                .map(BatchPayload::Sources),
+            _ => client
+                .send_spec_async(spec, sent, Instant::now())
+                .await
+                .map(BatchPayload::Articles),
+        }
+    }
+
+    #[cfg(feature = "blocking")]
+    fn send_sync(
+        client: &NewsAPIClient,
+        spec: &RequestSpec,
+        sent: &AtomicUsize,
+    ) -> Result<BatchPayload, NewsApiError> {
+        match spec {
+            RequestSpec::Sources(_) => client
+                .send_spec_sync(spec, sent, Instant::now())
+                .map(BatchPayload::Sources),
+            _ => client
+                .send_spec_sync(spec, sent, Instant::now())
+                .map(BatchPayload::Articles),
+        }
+    }
+}
+
+impl BatchResults {
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn new(results: Vec<Option<Result<BatchPayload, NewsApiError>>>, requests_sent: usize) -> Self {
+        BatchResults {
+            results: results
+                .into_iter()
+                .map(|result| result.expect("every request of the batch finished"))
+                .collect(),
+            requests_sent,
+        }
+    }
+
+    pub fn succeeded(&self) -> usize {
+        self.results.iter().filter(|result| result.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.succeeded()
+    }
+}
+
+/// NewsAPI said no key of the client has requests left today
+#[cfg(any(feature = "async", feature = "blocking"))]
+fn uses_up_quota<T>(client: &NewsAPIClient, result: &Result<T, NewsApiError>) -> bool {
+    matches!(result, Err(NewsApiError::QuotaExhausted)) || client.out_of_quota(result)
+}
+
+#[cfg(all(test, any(feature = "async", feature = "blocking")))]
+mod tests {
+    use super::*;
+    use crate::constants::{self, Language};
+    use crate::key::KeyPool;
+    use crate::metrics::Metrics;
+    use crate::spec::{EverythingSpec, SourcesSpec};
+    use crate::transport::{HttpResponse, MockTransport, Route};
+    use std::sync::Arc;
+
+    fn everything(query: &str) -> RequestSpec {
+        RequestSpec::Everything(EverythingSpec {
+            query: Some(query.to_owned()),
+            language: Some(Language::English),
+            ..EverythingSpec::default()
+        })
+    }
+
+    fn send_each_flavour(batch: &Batch, client: &NewsAPIClient) -> Vec<BatchResults> {
+        each_flavour!(batch.send_async(client), batch.send_sync(client))
+    }
+
+    #[test]
+    fn explain() {
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.country(constants::Country::Germany);
+        let invalid = RequestSpec::Everything(EverythingSpec {
+            query: Some("wasm".to_owned()),
+            page_size: Some(0),
+            ..EverythingSpec::default()
+        });
+        let batch = Batch::new().request(everything("rust")).request(invalid);
+
+        let explanations = batch.explain(&api);
+        assert_eq!(explanations.len(), 2);
+        // the client's own parameters are not part of the batch's requests
+        assert_eq!(
+            explanations[0].url,
+            "https://newsapi.org/v2/everything?q=rust&language=en"
+        );
+        assert!(explanations[0].warnings.is_empty());
+        assert_eq!(explanations[0].http_requests, 1);
+        assert_eq!(
+            explanations[1].warnings,
+            ["Unable to read a request from the URL: page_size=0 is not between 1 and 100"]
+        );
+        assert_eq!(explanations[1].http_requests, 0);
+    }
+
+    #[test]
+    fn in_order() {
+        let body = include_str!("../resources/example_everything.json");
+        let mock = Arc::new(
+            MockTransport::new()
+                .respond(
+                    Route::new(constants::EVERYTHING_URL).param("q", "rust"),
+                    HttpResponse::new(200, body),
+                )
+                .respond(
+                    Route::new(constants::EVERYTHING_URL).param("q", "wasm"),
+                    HttpResponse::new(400, "parameterInvalid"),
+                ),
+        );
+        let mut api = NewsAPIClient::new("123".to_owned());
+        // the client's own parameters do not leak into the batch
+        api.transport(mock)
+            .with_sources("bbc-news".to_owned())
+            .country(constants::Country::Germany);
+
+        let batch = Batch::new()
+            .requests(["rust", "wasm", "rust"].map(everything))
+            .concurrency(2);
+        for results in send_each_flavour(&batch, &api) {
+            assert_eq!(results.results.len(), 3);
+            let articles = results.results[0].as_ref().unwrap().articles().unwrap();
+            assert_eq!(articles.articles.len(), 2);
+            assert!(matches!(
+                results.results[1],
+                Err(NewsApiError::BadRequest { .. })
+            ));
+            assert!(results.results[2].is_ok());
+            assert_eq!(results.succeeded(), 2);
+            assert_eq!(results.failed(), 1);
+            assert_eq!(results.requests_sent, 3);
+        }
+    }
+
+    #[test]
+    fn mixed_endpoints() {
+        let mock = Arc::new(
+            MockTransport::new()
+                .respond(
+                    Route::new(constants::EVERYTHING_URL),
+                    HttpResponse::new(200, include_str!("../resources/example_everything.json")),
+                )
+                .respond(
+                    Route::new(constants::SOURCES_URL),
+                    HttpResponse::new(200, include_str!("../resources/example_sources.json")),
+                ),
+        );
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock);
+
+        let sources = RequestSpec::Sources(SourcesSpec {
+            language: Some(Language::English),
+            ..SourcesSpec::default()
+        });
+        let batch = Batch::new().request(everything("rust")).request(sources);
+        for results in send_each_flavour(&batch, &api) {
+            let payloads: Vec<_> = results
+                .results
+                .iter()
+                .map(|r| r.as_ref().unwrap())
+                .collect();
+            assert_eq!(payloads[0].articles().unwrap().articles.len(), 2);
+            assert_eq!(payloads[1].sources().unwrap().sources.len(), 4);
+            assert!(payloads[1].articles().is_none());
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn send_async_is_send() {
+        fn assert_send<T: Send>(_: T) {}
+        let api = NewsAPIClient::new("123".to_owned());
+        let batch = Batch::new().request(everything("rust"));
+        assert_send(batch.send_async(&api));
+    }
+
+    #[test]
+    fn stops_when_quota_is_used_up() {
+        let mock = Arc::new(MockTransport::new().respond(
+            Route::new(constants::EVERYTHING_URL),
+            HttpResponse::new(
+                429,
+                r#"{"status": "error", "code": "apiKeyExhausted", "message": "done"}"#,
+            ),
+        ));
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock.clone());
+
+        let batch = Batch::new()
+            .requests(["a", "b", "c", "d"].map(everything))
+            .concurrency(1);
+        for results in send_each_flavour(&batch, &api) {
+            assert!(matches!(
+                results.results[0],
+                Err(NewsApiError::TooManyRequests { .. })
+            ));
+            for result in &results.results[1..] {
+                assert!(matches!(result, Err(NewsApiError::QuotaExhausted)));
+            }
+            assert_eq!(results.requests_sent, 1);
+        }
+
+        // a quota known to be spent stops the batch before anything is sent
+        let mock = Arc::new(MockTransport::new());
+        api.transport(mock.clone())
+            .metrics(Arc::new(Metrics::with_daily_quota(0)));
+        for results in send_each_flavour(&batch, &api) {
+            assert_eq!(results.failed(), 4);
+            assert_eq!(results.requests_sent, 0);
+        }
+        assert!(mock.requests().is_empty());
+    }
+
+    #[test]
+    fn key_pool_with_one_key_spent() {
+        let body = include_str!("../resources/example_everything.json");
+        let exhausted = r#"{"status": "error", "code": "apiKeyExhausted", "message": "done"}"#;
+        let mock = Arc::new(MockTransport::new().respond(
+            Route::new(constants::EVERYTHING_URL),
+            HttpResponse::new(200, body),
+        ));
+        let mut api = NewsAPIClient::new("unused".to_owned());
+        api.transport(mock)
+            .key_pool(Arc::new(
+                KeyPool::new().key("spent", "key-a").key("fresh", "key-b"),
+            ))
+            .metrics(Arc::new(Metrics::with_daily_quota(100)))
+            .after_response(
+                move |request, response| match request.header_value("X-Api-Key") {
+                    Some("key-a") => Ok(HttpResponse::new(429, exhausted)),
+                    _ => Ok(response),
+                },
+            );
+
+        let batch = Batch::new()
+            .requests(["a", "b", "c", "d"].map(everything))
+            .concurrency(1);
+        // the spent key fails over to the other one, which keeps the batch going; after that
+        // it sits out until midnight
+        let results = send_each_flavour(&batch, &api);
+        for (run, results) in results.iter().enumerate() {
+            assert_eq!(results.succeeded(), 4);
+            assert_eq!(results.requests_sent, if run == 0 { 5 } else { 4 });
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use super::error::NewsApiError;
+use std::collections::HashMap;
+use std::future::{poll_fn, Future};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+
+/// Cancels the requests of every client it was given to, see `NewsAPIClient::cancel_with`.
+/// Clones share the same state, so one can be kept by whoever decides to cancel.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    /// Tasks awaiting an in-flight `send_async`, woken on cancel
+    wakers: Mutex<Wakers>,
+}
+
+#[derive(Debug, Default)]
+struct Wakers {
+    next_slot: u64,
+    slots: HashMap<u64, Waker>,
+}
+
+/// The slot of one in-flight `send_async` in its token, freed when the send finishes or is
+/// dropped
+struct Registration<'a> {
+    token: &'a CancellationToken,
+    slot: Option<u64>,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            self.token.inner.wakers.lock().unwrap().slots.remove(&slot);
+        }
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Fail requests that have not started yet, and abandon in-flight `send_async` requests,
+    /// with `NewsApiError::Cancelled`. A blocking request already in flight runs to completion.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        for (_, waker) in self.inner.wakers.lock().unwrap().slots.drain() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), NewsApiError> {
+        if self.is_cancelled() {
+            return Err(NewsApiError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// True if already cancelled, otherwise remember `waker` in the slot of `registration`
+    /// to wake on cancel
+    fn register(&self, registration: &mut Registration<'_>, waker: &Waker) -> bool {
+        let mut wakers = self.inner.wakers.lock().unwrap();
+        if self.is_cancelled() {
+            return true;
+        }
+        let slot = match registration.slot {
+            Some(slot) => slot,
+            None => {
+                wakers.next_slot += 1;
+                registration.slot = Some(wakers.next_slot);
+                wakers.next_slot
+            }
+        };
+        match wakers.slots.get_mut(&slot) {
+            Some(known) if known.will_wake(waker) => {}
+            Some(known) => *known = waker.clone(),
+            None => {
+                wakers.slots.insert(slot, waker.clone());
+            }
+        }
+        false
+    }
+}
+
+/// Run `future` unless `token` is cancelled first, in which case it is dropped
+pub(crate) async fn unless_cancelled<F, T>(
+    token: Option<&CancellationToken>,
+    future: F,
+) -> Result<T, NewsApiError>
+where
+    F: Future<Output = Result<T, NewsApiError>>,
+{
+    let token = match token {
+        Some(token) => token,
+        None => return future.await,
+    };
+
+    let mut future = std::pin::pin!(future);
+    let mut registration = Registration { token, slot: None };
+    poll_fn(|cx| {
+        if token.register(&mut registration, cx.waker()) {
+            return Poll::Ready(Err(NewsApiError::Cancelled));
+        }
+        future.as_mut().poll(cx)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execute::block_on;
+
+    #[test]
+    fn cancel() {
+        let token = CancellationToken::new();
+        let shared = token.clone();
+        assert!(token.check().is_ok());
+        assert_eq!(
+            block_on(unless_cancelled(Some(&token), async { Ok(1) })).unwrap(),
+            1
+        );
+
+        shared.cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(token.check(), Err(NewsApiError::Cancelled)));
+        assert!(matches!(
+            block_on(unless_cancelled(Some(&token), async { Ok(1) })),
+            Err(NewsApiError::Cancelled)
+        ));
+        assert_eq!(
+            block_on(unless_cancelled(None, async { Ok(1) })).unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn wakers_are_freed() {
+        let token = CancellationToken::new();
+        let mut cx = std::task::Context::from_waker(Waker::noop());
+        for _ in 0..100 {
+            let mut polled = false;
+            let pending_once = poll_fn(|_| {
+                if polled {
+                    return Poll::Ready(Ok(()));
+                }
+                polled = true;
+                Poll::Pending
+            });
+            let mut send = std::pin::pin!(unless_cancelled(Some(&token), pending_once));
+            assert!(send.as_mut().poll(&mut cx).is_pending());
+            assert_eq!(token.inner.wakers.lock().unwrap().slots.len(), 1);
+            assert!(send.as_mut().poll(&mut cx).is_ready());
+        }
+        assert!(token.inner.wakers.lock().unwrap().slots.is_empty());
+
+        // a send dropped before it finishes frees its slot too
+        let mut send = Box::pin(unless_cancelled(
+            Some(&token),
+            std::future::pending::<Result<(), NewsApiError>>(),
+        ));
+        assert!(send.as_mut().poll(&mut cx).is_pending());
+        drop(send);
+        assert!(token.inner.wakers.lock().unwrap().slots.is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+use super::error::NewsApiError;
+use super::trace;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// What a `CircuitBreaker` currently lets through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Every request goes to NewsAPI
+    Closed,
+    /// Requests fail straight away with `NewsApiError::CircuitOpen`
+    Open,
+    /// A limited number of probe requests go to NewsAPI to see whether it has recovered
+    HalfOpen,
+}
+
+/// Stops a client calling NewsAPI during an outage. Once enough of the recent requests failed
+/// because NewsAPI was unreachable, timing out or returning server errors, the circuit opens
+/// and requests fail with `CircuitOpen` (served from the stale cache by the `*_or_stale`
+/// methods, if configured). After `open_for` it half-opens and lets probe requests through:
+/// enough successful probes close it again, a failed one re-opens it.
+///
+/// Rate limiting and bad requests do not count as failures, they are about the key or the
+/// request rather than NewsAPI being down.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_ratio: f64,
+    minimum_requests: usize,
+    window: usize,
+    open_for: Duration,
+    probes: u32,
+    state: Mutex<Breaker>,
+}
+
+#[derive(Debug)]
+struct Breaker {
+    state: CircuitState,
+    /// Outcomes of the most recent requests while closed, `true` for a failure
+    outcomes: VecDeque<bool>,
+    opened_at: Option<Instant>,
+    probes_in_flight: u32,
+    probes_succeeded: u32,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> CircuitBreaker {
+        CircuitBreaker::new()
+    }
+}
+
+impl CircuitBreaker {
+    /// Opens when half of the last 20 requests (and at least 10) failed, stays open for 30
+    /// seconds and closes after one successful probe
+    pub fn new() -> CircuitBreaker {
+        CircuitBreaker {
+            failure_ratio: 0.5,
+            minimum_requests: 10,
+            window: 20,
+            open_for: Duration::from_secs(30),
+            probes: 1,
+            state: Mutex::new(Breaker {
+                state: CircuitState::Closed,
+                outcomes: VecDeque::new(),
+                opened_at: None,
+                probes_in_flight: 0,
+                probes_succeeded: 0,
+            }),
+        }
+    }
+
+    /// The share of failed requests, between 0 and 1, that opens the circuit
+    pub fn failure_ratio(mut self, ratio: f64) -> CircuitBreaker {
+        self.failure_ratio = ratio;
+        self
+    }
+
+    /// How many recent requests the failure ratio is worked out over
+    pub fn window(mut self, requests: usize) -> CircuitBreaker {
+        self.window = requests.max(1);
+        self
+    }
+
+    /// The circuit never opens before this many requests have been seen
+    pub fn minimum_requests(mut self, requests: usize) -> CircuitBreaker {
+        self.minimum_requests = requests;
+        self
+    }
+
+    /// How long the circuit stays open before probing
+    pub fn open_for(mut self, duration: Duration) -> CircuitBreaker {
+        self.open_for = duration;
+        self
+    }
+
+    /// How many successful probes close the circuit again
+    pub fn probes(mut self, probes: u32) -> CircuitBreaker {
+        self.probes = probes.max(1);
+        self
+    }
+
+    /// The current state, e.g. for a health check
+    pub fn state(&self) -> CircuitState {
+        let mut breaker = self.state.lock().unwrap();
+        self.half_open_if_due(&mut breaker);
+        breaker.state
+    }
+
+    /// Ask to send a request, failing with `CircuitOpen` if the circuit does not allow it
+    pub(crate) fn permit(&self) -> Result<Permit<'_>, NewsApiError> {
+        let mut breaker = self.state.lock().unwrap();
+        self.half_open_if_due(&mut breaker);
+
+        match breaker.state {
+            CircuitState::Closed => Ok(Permit {
+                breaker: self,
+                probe: false,
+            }),
+            CircuitState::HalfOpen if breaker.probes_in_flight < self.probes => {
+                breaker.probes_in_flight += 1;
+                Ok(Permit {
+                    breaker: self,
+                    probe: true,
+                })
+            }
+            _ => Err(NewsApiError::CircuitOpen),
+        }
+    }
+
+    fn half_open_if_due(&self, breaker: &mut Breaker) {
+        let due = breaker
+            .opened_at
+            .is_some_and(|opened_at| opened_at.elapsed() >= self.open_for);
+        if breaker.state == CircuitState::Open && due {
+            self.transition(breaker, CircuitState::HalfOpen);
+        }
+    }
+
+    fn transition(&self, breaker: &mut Breaker, state: CircuitState) {
+        breaker.state = state;
+        breaker.probes_in_flight = 0;
+        breaker.probes_succeeded = 0;
+        match state {
+            CircuitState::Open => breaker.opened_at = Some(Instant::now()),
+            CircuitState::Closed => breaker.outcomes.clear(),
+            CircuitState::HalfOpen => {}
+        }
+        trace::circuit(state);
+    }
+
+    fn record(&self, probe: bool, failed: bool) {
+        let mut breaker = self.state.lock().unwrap();
+
+        if probe {
+            breaker.probes_in_flight = breaker.probes_in_flight.saturating_sub(1);
+            if breaker.state != CircuitState::HalfOpen {
+                return;
+            }
+            if failed {
+                self.transition(&mut breaker, CircuitState::Open);
+            } else {
+                breaker.probes_succeeded += 1;
+                if breaker.probes_succeeded >= self.probes {
+                    self.transition(&mut breaker, CircuitState::Closed);
+                }
+            }
+            return;
+        }
+
+        if breaker.state != CircuitState::Closed {
+            return;
+        }
+        breaker.outcomes.push_back(failed);
+        while breaker.outcomes.len() > self.window {
+            breaker.outcomes.pop_front();
+        }
+
+        let seen = breaker.outcomes.len();
+        let failures = breaker.outcomes.iter().filter(|failed| **failed).count();
+        if seen >= self.minimum_requests && failures as f64 >= self.failure_ratio * seen as f64 {
+            self.transition(&mut breaker, CircuitState::Open);
+        }
+    }
+}
+
+/// Leave to send one request. Report how it went with `record`; a permit dropped without
+/// recording (e.g. a cancelled request) counts as neither success nor failure.
+#[derive(Debug)]
+pub(crate) struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+}
+
+impl Permit<'_> {
+    pub(crate) fn record<T>(mut self, result: &Result<T, NewsApiError>) {
+        let failed = match result {
+            Ok(_) => false,
+            Err(error) => is_outage(error),
+        };
+        self.breaker.record(self.probe, failed);
+        self.probe = false;
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            let mut breaker = self.breaker.state.lock().unwrap();
+            breaker.probes_in_flight = breaker.probes_in_flight.saturating_sub(1);
+        }
+    }
+}
+
+/// Errors that say NewsAPI is down, as opposed to the key being limited or the request wrong
+fn is_outage(error: &NewsApiError) -> bool {
+    error.is_unavailable() && !matches!(error, NewsApiError::TooManyRequests { .. })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outage() -> Result<(), NewsApiError> {
+        Err(NewsApiError::ServerError {
+            code: 503,
+            message: "down".into(),
+        })
+    }
+
+    #[test]
+    fn opens_and_recovers() {
+        let breaker = CircuitBreaker::new()
+            .window(4)
+            .minimum_requests(4)
+            .failure_ratio(0.5)
+            .open_for(Duration::ZERO)
+            .probes(2);
+
+        breaker
+            .permit()
+            .unwrap()
+            .record(&Ok::<(), NewsApiError>(()));
+        breaker.permit().unwrap().record(&outage());
+        breaker
+            .permit()
+            .unwrap()
+            .record(&Err::<(), _>(NewsApiError::TooManyRequests {
+                code: 429,
+                message: "limited".into(),
+            }));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        breaker.permit().unwrap().record(&outage());
+
+        // open_for is zero, so the open circuit half-opens as soon as it is looked at
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        let first = breaker.permit().unwrap();
+        let second = breaker.permit().unwrap();
+        assert!(matches!(breaker.permit(), Err(NewsApiError::CircuitOpen)));
+
+        first.record(&Ok::<(), NewsApiError>(()));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        second.record(&Ok::<(), NewsApiError>(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn failed_probe_reopens() {
+        let breaker = CircuitBreaker::new()
+            .minimum_requests(1)
+            .open_for(Duration::from_secs(60));
+
+        breaker.permit().unwrap().record(&outage());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(matches!(breaker.permit(), Err(NewsApiError::CircuitOpen)));
+
+        breaker.state.lock().unwrap().opened_at = Some(Instant::now() - Duration::from_secs(61));
+        let probe = breaker.permit().unwrap();
+        probe.record(&outage());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // an abandoned probe frees its slot
+        breaker.state.lock().unwrap().opened_at = Some(Instant::now() - Duration::from_secs(61));
+        drop(breaker.permit().unwrap());
+        assert!(breaker.permit().is_ok());
+    }
+}
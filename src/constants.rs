@@ -93,92 +93,163 @@ lazy_static! {
 
 }
 
-#[derive(Debug, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum, Serialize, Deserialize)]
 pub enum SortMethod {
+    #[serde(rename = "relevancy")]
     Relevancy,
+    #[serde(rename = "popularity")]
     Popularity,
+    #[serde(rename = "publishedAt")]
     PublishedAt,
 }
 
-#[derive(Debug, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum, Serialize, Deserialize)]
 pub enum Country {
+    #[serde(rename = "ar")]
     Argentina,
+    #[serde(rename = "au")]
     Australia,
+    #[serde(rename = "at")]
     Austria,
+    #[serde(rename = "be")]
     Belgium,
+    #[serde(rename = "br")]
     Brazil,
+    #[serde(rename = "bg")]
     Bulgaria,
+    #[serde(rename = "ca")]
     Canada,
+    #[serde(rename = "cn")]
     China,
+    #[serde(rename = "co")]
     Colombia,
+    #[serde(rename = "cu")]
     Cuba,
+    #[serde(rename = "cz")]
     Czechia,
+    #[serde(rename = "eg")]
     Egypt,
+    #[serde(rename = "fr")]
     France,
+    #[serde(rename = "de")]
     Germany,
+    #[serde(rename = "gr")]
     Greece,
+    #[serde(rename = "hk")]
     HongKong,
+    #[serde(rename = "hu")]
     Hungary,
+    #[serde(rename = "in")]
     India,
+    #[serde(rename = "id")]
     Indonesia,
+    #[serde(rename = "ie")]
     Ireland,
+    #[serde(rename = "il")]
     Israel,
+    #[serde(rename = "it")]
     Italy,
+    #[serde(rename = "jp")]
     Japan,
+    #[serde(rename = "kr")]
     KoreaRepublicof,
+    #[serde(rename = "lv")]
     Latvia,
+    #[serde(rename = "lt")]
     Lithuania,
+    #[serde(rename = "my")]
     Malaysia,
+    #[serde(rename = "mx")]
     Mexico,
+    #[serde(rename = "ma")]
     Morocco,
+    #[serde(rename = "nl")]
     Netherlands,
+    #[serde(rename = "nz")]
     NewZealand,
+    #[serde(rename = "ng")]
     Nigeria,
+    #[serde(rename = "no")]
     Norway,
+    #[serde(rename = "ph")]
     Philippines,
+    #[serde(rename = "pl")]
     Poland,
+    #[serde(rename = "pt")]
     Portugal,
+    #[serde(rename = "ro")]
     Romania,
+    #[serde(rename = "ru")]
     RussianFederation,
+    #[serde(rename = "sa")]
     SaudiArabia,
+    #[serde(rename = "rs")]
     Serbia,
+    #[serde(rename = "sg")]
     Singapore,
+    #[serde(rename = "sk")]
     Slovakia,
+    #[serde(rename = "si")]
     Slovenia,
+    #[serde(rename = "za")]
     SouthAfrica,
+    #[serde(rename = "se")]
     Sweden,
+    #[serde(rename = "ch")]
     Switzerland,
+    #[serde(rename = "tw")]
     Taiwan,
+    #[serde(rename = "th")]
     Thailand,
+    #[serde(rename = "tr")]
     Turkey,
+    #[serde(rename = "ua")]
     Ukraine,
+    #[serde(rename = "ae")]
     UnitedArabEmirates,
+    #[serde(rename = "gb")]
     UnitedKingdomofGreatBritainandNorthernIreland,
+    #[serde(rename = "us")]
     UnitedStatesofAmerica,
+    #[serde(rename = "ve")]
     VenezuelaBolivarianRepublicof,
 }
 
 // Language list below obtained from NewsAPI docs on 01/04/2019 https://newsapi.org/docs/endpoints/everything
 // "ar", "en", "cn", "de", "es", "fr", "he", "it", "nl", "no", "pt", "ru", "sv", "ud",
-#[derive(Debug, Enum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum, Serialize, Deserialize)]
 pub enum Language {
+    #[serde(rename = "ar")]
     Arabic,
+    #[serde(rename = "de")]
     German,
+    #[serde(rename = "en")]
     English,
+    #[serde(rename = "es")]
     Spanish,
+    #[serde(rename = "fr")]
     French,
+    #[serde(rename = "he")]
     Hebrew,
+    #[serde(rename = "it")]
     Italian,
+    #[serde(rename = "nl")]
     Dutch,
+    #[serde(rename = "no")]
     Norwegian,
+    #[serde(rename = "pt")]
     Portuguese,
+    #[serde(rename = "ru")]
     Russian,
+    #[serde(rename = "se")]
     NorthernSami,
+    #[serde(rename = "ud")]
     Urdu,
+    #[serde(rename = "zh")]
     Chinese,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Category {
     #[serde(rename = "business")]
     Business,
@@ -195,3 +266,32 @@ pub enum Category {
     #[serde(rename = "technology")]
     Technology,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use enum_map::EnumArray;
+    use serde::Serialize;
+
+    /// Specs store the serde names and the client sends the lookups, so the two must not drift
+    /// apart
+    fn matches_lookup<K: EnumArray<&'static str> + Serialize + std::fmt::Debug>(
+        lookup: &EnumMap<K, &'static str>,
+    ) {
+        for (variant, code) in lookup.iter() {
+            assert_eq!(
+                serde_json::to_value(&variant).unwrap(),
+                *code,
+                "{:?}",
+                variant
+            );
+        }
+    }
+
+    #[test]
+    fn serde_names_match_lookups() {
+        matches_lookup(&COUNTRY_LOOKUP);
+        matches_lookup(&LANG_LOOKUP);
+        matches_lookup(&SORT_METHOD_LOOKUP);
+    }
+}
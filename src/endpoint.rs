@@ -0,0 +1,76 @@
+use super::constants;
+use super::payload::article::Articles;
+use super::payload::source::Sources as SourceList;
+use serde::de::DeserializeOwned;
+
+/// A NewsAPI endpoint: where it lives, which parameters it accepts and what it returns
+pub trait Endpoint {
+    /// What a successful response from this endpoint decodes into
+    type Payload: DeserializeOwned;
+
+    const URL: &'static str;
+
+    /// The query parameters sent to this endpoint, in the order they appear in the URL
+    const PARAMETERS: &'static [&'static str];
+}
+
+/// The last path segment of an endpoint URL, e.g. `top-headlines`
+pub(crate) fn name_of(url: &str) -> &str {
+    let path = url.split_once('?').map(|(path, _)| path).unwrap_or(url);
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// The parameters taken by the endpoint at `url`, if it is one
+pub(crate) fn parameters_of(url: &str) -> Option<&'static [&'static str]> {
+    if url == Everything::URL {
+        Some(Everything::PARAMETERS)
+    } else if url == TopHeadlines::URL {
+        Some(TopHeadlines::PARAMETERS)
+    } else if url == Sources::URL {
+        Some(Sources::PARAMETERS)
+    } else {
+        None
+    }
+}
+
+/// https://newsapi.org/docs/endpoints/everything
+#[derive(Debug)]
+pub struct Everything;
+
+impl Endpoint for Everything {
+    type Payload = Articles;
+    const URL: &'static str = constants::EVERYTHING_URL;
+    const PARAMETERS: &'static [&'static str] = &[
+        "q",
+        "sources",
+        "domains",
+        "excludeDomains",
+        "from",
+        "to",
+        "language",
+        "sortBy",
+        "pageSize",
+        "page",
+    ];
+}
+
+/// https://newsapi.org/docs/endpoints/top-headlines
+#[derive(Debug)]
+pub struct TopHeadlines;
+
+impl Endpoint for TopHeadlines {
+    type Payload = Articles;
+    const URL: &'static str = constants::TOP_HEADLINES_URL;
+    const PARAMETERS: &'static [&'static str] =
+        &["q", "country", "category", "sources", "pageSize", "page"];
+}
+
+/// https://newsapi.org/docs/endpoints/sources
+#[derive(Debug)]
+pub struct Sources;
+
+impl Endpoint for Sources {
+    type Payload = SourceList;
+    const URL: &'static str = constants::SOURCES_URL;
+    const PARAMETERS: &'static [&'static str] = &["category", "language", "country"];
+}
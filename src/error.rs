@@ -1,13 +1,143 @@
+use super::payload::status::ApiError;
 use custom_error::custom_error;
+use std::borrow::Borrow;
+use std::error::Error;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
 
-custom_error! { pub NewsApiError
+custom_error! {
+    /// Clones share the reqwest or serde error they wrap, so one failure can be handed to
+    /// every caller waiting on it
+    #[derive(Clone)]
+    pub NewsApiError
     InvalidParameterCombinationError = "The source parameter cannot be mixed with country or category",
-    UndefinedUrlError = "Error constructing newsapi URL",
+    InvalidUrlError{message: String} = "Unable to read a request from the URL: {message}",
+    EndpointMismatch{expected: String, found: String} = "Expected a request for {expected}, got one for {found}",
+    ApiKeyError{message: String} = "Unable to load the API key: {message}",
     GenericError{code: u16, message: String} = "GenericError: {code} => {message}",
     BadRequest{code: u16, message: String} = "BadRequest: {code} => {message}",
     Unauthorized{code: u16, message: String} = "Unauthorized: {code} => {message}",
     TooManyRequests{code: u16, message: String} = "TooManyRequests: {code} => {message}",
     ServerError{code: u16, message: String} = "ServerError: {code} => {message}",
-    ReqwestError{source: reqwest::Error} = "Reqwest Failure!",
-    SerdeError{source: serde_json::error::Error} = "Unable to deserialize payload",
+    ReqwestError{source: Shared<reqwest::Error>} = "Reqwest Failure!",
+    TransportError{message: String} = "Transport failure: {message}",
+    Timeout = "The request did not complete before its timeout or deadline",
+    Cancelled = "The request was cancelled",
+    CircuitOpen = "The circuit breaker is open, NewsAPI was not called",
+    QuotaExhausted = "The daily quota is used up, NewsAPI was not called",
+    WatchStateError{message: String} = "Unable to read or write the seen articles of a watch: {message}",
+    SerdeError{source: Shared<serde_json::error::Error>} = "Unable to deserialize payload",
+}
+
+impl NewsApiError {
+    /// True when NewsAPI could not serve the request right now (quota exhausted, rate limited,
+    /// server side failure, unreachable or the circuit breaker is open) as opposed to the
+    /// request itself being wrong.
+    pub fn is_unavailable(&self) -> bool {
+        match self {
+            NewsApiError::TooManyRequests { .. }
+            | NewsApiError::ServerError { .. }
+            | NewsApiError::ReqwestError { .. }
+            | NewsApiError::TransportError { .. }
+            | NewsApiError::Timeout
+            | NewsApiError::CircuitOpen
+            | NewsApiError::QuotaExhausted => true,
+            NewsApiError::GenericError { code, .. } => *code >= 500,
+            _ => false,
+        }
+    }
+
+    /// The name of the variant, e.g. for labelling metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NewsApiError::InvalidParameterCombinationError => "InvalidParameterCombinationError",
+            NewsApiError::InvalidUrlError { .. } => "InvalidUrlError",
+            NewsApiError::EndpointMismatch { .. } => "EndpointMismatch",
+            NewsApiError::ApiKeyError { .. } => "ApiKeyError",
+            NewsApiError::GenericError { .. } => "GenericError",
+            NewsApiError::BadRequest { .. } => "BadRequest",
+            NewsApiError::Unauthorized { .. } => "Unauthorized",
+            NewsApiError::TooManyRequests { .. } => "TooManyRequests",
+            NewsApiError::ServerError { .. } => "ServerError",
+            NewsApiError::ReqwestError { .. } => "ReqwestError",
+            NewsApiError::TransportError { .. } => "TransportError",
+            NewsApiError::Timeout => "Timeout",
+            NewsApiError::Cancelled => "Cancelled",
+            NewsApiError::CircuitOpen => "CircuitOpen",
+            NewsApiError::QuotaExhausted => "QuotaExhausted",
+            NewsApiError::WatchStateError { .. } => "WatchStateError",
+            NewsApiError::SerdeError { .. } => "SerdeError",
+        }
+    }
+
+    /// The error body NewsAPI sent, if there was one, e.g. to tell `apiKeyExhausted` from
+    /// `rateLimited`
+    pub fn api_error(&self) -> Option<ApiError> {
+        match self {
+            NewsApiError::GenericError { message, .. }
+            | NewsApiError::BadRequest { message, .. }
+            | NewsApiError::Unauthorized { message, .. }
+            | NewsApiError::TooManyRequests { message, .. }
+            | NewsApiError::ServerError { message, .. } => serde_json::from_str(message).ok(),
+            _ => None,
+        }
+    }
+
+    /// NewsAPI said the key has no requests left today
+    pub(crate) fn exhausts_quota(&self) -> bool {
+        self.api_error().map(|e| e.code).as_deref() == Some("apiKeyExhausted")
+    }
+}
+
+impl From<reqwest::Error> for NewsApiError {
+    fn from(source: reqwest::Error) -> Self {
+        NewsApiError::ReqwestError {
+            source: Shared(Arc::new(source)),
+        }
+    }
+}
+
+impl From<serde_json::Error> for NewsApiError {
+    fn from(source: serde_json::Error) -> Self {
+        NewsApiError::SerdeError {
+            source: Shared(Arc::new(source)),
+        }
+    }
+}
+
+/// An error wrapped by a `NewsApiError`, shared by its clones. Derefs to the wrapped error.
+#[derive(Debug)]
+pub struct Shared<E>(Arc<E>);
+
+impl<E> Clone for Shared<E> {
+    fn clone(&self) -> Self {
+        Shared(self.0.clone())
+    }
+}
+
+impl<E> Deref for Shared<E> {
+    type Target = E;
+
+    fn deref(&self) -> &E {
+        &self.0
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Shared<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<E: Error> Error for Shared<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+impl<E: Error + 'static> Borrow<dyn Error + 'static> for Shared<E> {
+    fn borrow(&self) -> &(dyn Error + 'static) {
+        &*self.0
+    }
 }
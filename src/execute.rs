@@ -0,0 +1,64 @@
+use super::error::NewsApiError;
+#[cfg(feature = "async")]
+use super::transport::AsyncTransport;
+#[cfg(feature = "blocking")]
+use super::transport::BlockingTransport;
+use super::transport::{HttpRequest, HttpResponse};
+#[cfg(any(feature = "blocking", test))]
+use std::future::Future;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(any(feature = "blocking", test))]
+use std::task::{Context, Poll, Waker};
+
+/// The I/O half of the execution pipeline. Everything else (validation, building the request,
+/// error mapping, caching and decoding) is written once against this trait, so the sync and
+/// async paths cannot drift apart.
+pub(crate) trait Flavour {
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError>;
+}
+
+#[cfg(feature = "async")]
+pub(crate) struct AsyncFlavour<'a>(pub &'a dyn AsyncTransport);
+
+#[cfg(feature = "async")]
+impl Flavour for AsyncFlavour<'_> {
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError> {
+        self.0.execute(request).await
+    }
+}
+
+/// Performs the I/O inline, so a pipeline run with it never suspends and can be driven to
+/// completion by `block_on`.
+#[cfg(feature = "blocking")]
+pub(crate) struct BlockingFlavour<'a>(pub &'a dyn BlockingTransport);
+
+#[cfg(feature = "blocking")]
+impl Flavour for BlockingFlavour<'_> {
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError> {
+        self.0.execute_blocking(request)
+    }
+}
+
+/// Counts the requests that reach the transport, e.g. for a batch summary
+#[cfg(any(feature = "async", feature = "blocking"))]
+pub(crate) struct Counted<'a, F>(pub F, pub &'a AtomicUsize);
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+impl<F: Flavour> Flavour for Counted<'_, F> {
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError> {
+        self.1.fetch_add(1, Ordering::SeqCst);
+        self.0.execute(request).await
+    }
+}
+
+/// Run a pipeline that never suspends, i.e. one using `BlockingFlavour`.
+#[cfg(any(feature = "blocking", test))]
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    match future.as_mut().poll(&mut cx) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("blocking pipeline suspended"),
+    }
+}
@@ -0,0 +1,105 @@
+use super::key::{
+    is_secret_header, redact_header, redact_url, AuthMode, API_KEY_ENV, API_KEY_PARAMETER,
+};
+use super::transport::HttpRequest;
+use std::fmt;
+
+/// What a send would do, from `Request::explain` or, one per request, `Batch::explain`. Nothing
+/// is sent and middleware is not run, so hooks that rewrite requests are not reflected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// The URL that would be requested, with the key redacted
+    pub url: String,
+    /// The headers that would be sent, with the key redacted
+    pub headers: Vec<(String, String)>,
+    /// Problems spotted without asking NewsAPI, e.g. parameters the endpoint ignores
+    pub warnings: Vec<String>,
+    /// HTTP requests a send makes when nothing fails: one, or none when a warning says the send
+    /// fails before calling NewsAPI. A sync makes one such send per page.
+    pub http_requests: usize,
+    /// HTTP requests a send makes at most, when the keys of a key pool fail over in turn
+    pub max_http_requests: usize,
+    /// A `curl` command making the same request, reading the key from `$NEWSAPI_KEY`
+    pub curl: String,
+}
+
+impl Explanation {
+    /// `url` is the request URL before the key was added, `request` what would be sent
+    pub(crate) fn new(
+        url: &str,
+        request: &HttpRequest,
+        auth_mode: AuthMode,
+        warnings: Vec<String>,
+        http_requests: usize,
+        max_http_requests: usize,
+    ) -> Explanation {
+        Explanation {
+            url: redact_url(&request.url),
+            headers: request
+                .headers
+                .iter()
+                .map(|(name, value)| (name.clone(), redact_header(name, value).to_owned()))
+                .collect(),
+            warnings,
+            http_requests,
+            max_http_requests,
+            curl: curl(url, request, auth_mode),
+        }
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "GET {}", self.url)?;
+        for (name, value) in &self.headers {
+            writeln!(f, "{name}: {value}")?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {warning}")?;
+        }
+        if self.max_http_requests > self.http_requests {
+            writeln!(
+                f,
+                "HTTP requests: {} (up to {} with key failover)",
+                self.http_requests, self.max_http_requests
+            )?;
+        } else {
+            writeln!(f, "HTTP requests: {}", self.http_requests)?;
+        }
+        write!(f, "{}", self.curl)
+    }
+}
+
+fn curl(url: &str, request: &HttpRequest, auth_mode: AuthMode) -> String {
+    let key = format!("\"${API_KEY_ENV}\"");
+    let mut command = String::from("curl");
+    if let Some(timeout) = request.timeout {
+        command.push_str(&format!(" --max-time {}", timeout.as_secs_f64()));
+    }
+    for (name, value) in &request.headers {
+        if !is_secret_header(name) {
+            command.push_str(&format!(" -H {}", quote(&format!("{name}: {value}"))));
+        }
+    }
+    match auth_mode {
+        AuthMode::Header => command.push_str(&format!(" -H 'X-Api-Key: '{key}")),
+        AuthMode::Bearer => command.push_str(&format!(" -H 'Authorization: Bearer '{key}")),
+        AuthMode::QueryParameter => {
+            let separator = if url.contains('?') { '&' } else { '?' };
+            command.push_str(&format!(
+                " {}{}",
+                quote(&format!("{url}{separator}{API_KEY_PARAMETER}=")),
+                key
+            ));
+            return command;
+        }
+    }
+    command.push(' ');
+    command.push_str(&quote(url));
+    command
+}
+
+/// `value` single-quoted for a POSIX shell
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
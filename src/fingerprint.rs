@@ -0,0 +1,117 @@
+use percent_encoding::percent_decode_str;
+use std::fmt;
+
+/// Identifies a request by what it asks NewsAPI for: the endpoint and its query parameters,
+/// regardless of parameter order. The API key is never part of it. Stable across builds and
+/// platforms, so it can be logged and compared between processes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    pub fn of_url(url: &str) -> Fingerprint {
+        let (base_url, query) = url.split_once('?').unwrap_or((url, ""));
+        let mut params: Vec<(String, String)> = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                // split before decoding, so an encoded `&` or `=` in a value stays in it
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(name), decode(value))
+            })
+            .collect();
+        params.sort();
+
+        let mut hash = Fnv1a::new();
+        hash.write_field(base_url.as_bytes());
+        for (name, value) in params {
+            hash.write_field(name.as_bytes());
+            hash.write_field(value.as_bytes());
+        }
+        Fingerprint(hash.0)
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+fn decode(text: &str) -> String {
+    percent_decode_str(text).decode_utf8_lossy().into_owned()
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+// std's DefaultHasher is not guaranteed to be stable between Rust releases, FNV-1a is.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    /// Write `bytes` after their length, so no two sequences of fields hash the same bytes
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameter_order_is_ignored() {
+        let a = Fingerprint::of_url("https://newsapi.org/v2/sources?language=en&country=us");
+        let b = Fingerprint::of_url("https://newsapi.org/v2/sources?country=us&language=en");
+        let c = Fingerprint::of_url("https://newsapi.org/v2/sources?country=gb&language=en");
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.to_string().len(), 16);
+    }
+
+    #[test]
+    fn stable() {
+        let fingerprint = Fingerprint::of_url("https://newsapi.org/v2/sources");
+        assert_eq!(
+            fingerprint,
+            Fingerprint::of_url("https://newsapi.org/v2/sources")
+        );
+        assert_ne!(
+            fingerprint,
+            Fingerprint::of_url("https://newsapi.org/v2/everything")
+        );
+    }
+
+    #[test]
+    fn encoded_separators_are_values() {
+        let smuggled =
+            Fingerprint::of_url("https://newsapi.org/v2/everything?q=a%26sortBy%3Dpopularity");
+        let separate =
+            Fingerprint::of_url("https://newsapi.org/v2/everything?q=a&sortBy=popularity");
+        assert_ne!(smuggled, separate);
+
+        let equals = Fingerprint::of_url("https://newsapi.org/v2/everything?q=a%3Db");
+        assert_ne!(
+            equals,
+            Fingerprint::of_url("https://newsapi.org/v2/everything?q%3Da=b")
+        );
+        // encoding alone does not change what is asked for
+        assert_eq!(
+            Fingerprint::of_url("https://newsapi.org/v2/everything?q=rust%20lang"),
+            Fingerprint::of_url("https://newsapi.org/v2/everything?q=rust lang")
+        );
+    }
+}
@@ -0,0 +1,497 @@
+//! Incremental sync of saved everything searches: each run asks only for articles published
+//! since the last run that was acknowledged.
+//!
+//! A `SyncState` keeps a `Watermark` per named search: the latest `publishedAt` seen and the
+//! URLs of the articles published at that instant. The next fetch sets `from` to the
+//! watermark, sorts by `publishedAt` and pages through every match the plan may read, then
+//! drops repeats and the articles the watermark already covers, since `from` is inclusive. The watermark only moves when the
+//! caller acknowledges a `SyncBatch`, so a run that fails half way through its articles
+//! fetches them again next time. The state is plain data; store it between runs with serde.
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+use super::api::NewsAPIClient;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use super::constants::SortMethod;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use super::error::NewsApiError;
+use super::payload::article::Article;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use super::payload::article::Articles;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use super::spec::{EverythingSpec, RequestSpec};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::sync::atomic::AtomicUsize;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::time::Instant;
+
+/// How far a search has been synced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Watermark {
+    /// The latest `publishedAt` of the articles processed so far
+    pub published_at: DateTime<Utc>,
+    /// The URLs of the processed articles published at exactly `published_at`
+    pub urls: Vec<String>,
+}
+
+/// The watermark of every search, by name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncState {
+    watermarks: BTreeMap<String, Watermark>,
+}
+
+/// The articles a search turned up since its watermark, oldest first
+#[derive(Debug)]
+pub struct SyncBatch {
+    pub name: String,
+    pub articles: Vec<Article>,
+    /// Where the watermark moves to when this batch is acknowledged
+    pub watermark: Option<Watermark>,
+}
+
+impl SyncState {
+    pub fn new() -> SyncState {
+        SyncState::default()
+    }
+
+    /// How far `name` has been synced, if it ever has
+    pub fn watermark(&self, name: &str) -> Option<&Watermark> {
+        self.watermarks.get(name)
+    }
+
+    /// Forget the watermark of `name`, so its next fetch starts over
+    pub fn reset(&mut self, name: &str) {
+        self.watermarks.remove(name);
+    }
+
+    /// Fetch the articles matching `spec` published since the watermark of `name`, a page of
+    /// `page_size` (100 by default) at a time. Its `sort_by` and `page` are ignored. Paging
+    /// stops early when NewsAPI answers `maximumResultsReached`, the most a plan may page
+    /// through, keeping the newest articles. If any other page fails, or the client's deadline
+    /// passes before the last page, the whole fetch fails and the next one starts from the
+    /// same watermark.
+    #[cfg(feature = "async")]
+    pub async fn fetch_async(
+        &self,
+        client: &NewsAPIClient,
+        name: &str,
+        spec: &EverythingSpec,
+    ) -> Result<SyncBatch, NewsApiError> {
+        let started = Instant::now();
+        let mut pages = Pages::new(self.since(name, spec));
+        while let Some(spec) = pages.next_spec() {
+            let page = client
+                .send_spec_async::<Articles>(&spec, &AtomicUsize::new(0), started)
+                .await;
+            pages.add(page)?;
+        }
+        Ok(self.batch(name, pages.articles))
+    }
+
+    /// Fetch the articles matching `spec` published since the watermark of `name`, a page of
+    /// `page_size` (100 by default) at a time. Its `sort_by` and `page` are ignored. Paging
+    /// stops early when NewsAPI answers `maximumResultsReached`, the most a plan may page
+    /// through, keeping the newest articles. If any other page fails, or the client's deadline
+    /// passes before the last page, the whole fetch fails and the next one starts from the
+    /// same watermark.
+    #[cfg(feature = "blocking")]
+    pub fn fetch_sync(
+        &self,
+        client: &NewsAPIClient,
+        name: &str,
+        spec: &EverythingSpec,
+    ) -> Result<SyncBatch, NewsApiError> {
+        let started = Instant::now();
+        let mut pages = Pages::new(self.since(name, spec));
+        while let Some(spec) = pages.next_spec() {
+            pages.add(client.send_spec_sync::<Articles>(&spec, &AtomicUsize::new(0), started))?;
+        }
+        Ok(self.batch(name, pages.articles))
+    }
+
+    /// Record that every article of `batch` was processed, moving its search's watermark
+    /// forward. Acknowledging an older batch after a newer one does not move it back.
+    pub fn acknowledge(&mut self, batch: &SyncBatch) {
+        let next = match &batch.watermark {
+            Some(next) => next,
+            None => return,
+        };
+        match self.watermarks.get_mut(&batch.name) {
+            Some(current) if current.published_at > next.published_at => {}
+            Some(current) if current.published_at == next.published_at => {
+                for url in &next.urls {
+                    if !current.urls.contains(url) {
+                        current.urls.push(url.clone());
+                    }
+                }
+            }
+            _ => {
+                self.watermarks.insert(batch.name.clone(), next.clone());
+            }
+        }
+    }
+
+    /// `spec` limited to articles published no earlier than the watermark of `name`, newest
+    /// first so the pages do not overlap
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn since(&self, name: &str, spec: &EverythingSpec) -> EverythingSpec {
+        let mut spec = spec.clone();
+        if let Some(watermark) = self.watermark(name) {
+            spec.from = Some(match spec.from {
+                Some(from) => from.max(watermark.published_at),
+                None => watermark.published_at,
+            });
+        }
+        spec.sort_by = Some(SortMethod::PublishedAt);
+        spec
+    }
+
+    /// The articles of `fetched` not covered by the watermark of `name`, each once: pages
+    /// overlap when articles are published while they are fetched
+    fn batch(&self, name: &str, fetched: Vec<Article>) -> SyncBatch {
+        let previous = self.watermark(name);
+
+        let mut urls = HashSet::new();
+        let mut articles: Vec<(Option<DateTime<Utc>>, Article)> = fetched
+            .into_iter()
+            .filter(|article| urls.insert(article.url.clone()))
+            .map(|article| (published_at(&article), article))
+            .filter(|(published, article)| match (previous, published) {
+                (Some(previous), Some(published)) => {
+                    *published > previous.published_at
+                        || (*published == previous.published_at
+                            && !previous.urls.contains(&article.url))
+                }
+                _ => true,
+            })
+            .collect();
+        // articles without a readable date go first, as nothing says they are newer
+        articles.sort_by_key(|(published, _)| *published);
+
+        let mut watermark: Option<Watermark> = None;
+        for (published, article) in &articles {
+            let published = match published {
+                Some(published) => *published,
+                None => continue,
+            };
+            match &mut watermark {
+                Some(latest) if latest.published_at == published => {
+                    latest.urls.push(article.url.clone())
+                }
+                _ => {
+                    watermark = Some(Watermark {
+                        published_at: published,
+                        urls: vec![article.url.clone()],
+                    })
+                }
+            }
+        }
+
+        SyncBatch {
+            name: name.to_owned(),
+            articles: articles.into_iter().map(|(_, article)| article).collect(),
+            watermark,
+        }
+    }
+}
+
+/// The pages of one fetch, asked for one after another until NewsAPI runs out of matches
+#[cfg(any(feature = "async", feature = "blocking"))]
+struct Pages {
+    spec: EverythingSpec,
+    page: u32,
+    page_size: u32,
+    articles: Vec<Article>,
+    done: bool,
+}
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+impl Pages {
+    fn new(spec: EverythingSpec) -> Pages {
+        Pages {
+            page_size: spec.page_size.unwrap_or(100),
+            spec,
+            page: 1,
+            articles: vec![],
+            done: false,
+        }
+    }
+
+    /// The spec for the next page, or `None` once every match was fetched
+    fn next_spec(&self) -> Option<RequestSpec> {
+        if self.done {
+            return None;
+        }
+        Some(RequestSpec::Everything(EverythingSpec {
+            page_size: Some(self.page_size),
+            page: Some(self.page),
+            ..self.spec.clone()
+        }))
+    }
+
+    /// Take in the next page. `maximumResultsReached` for a later page ends the fetch like
+    /// running out of matches does.
+    fn add(&mut self, page: Result<Articles, NewsApiError>) -> Result<(), NewsApiError> {
+        let page = match page {
+            Err(error) if self.page > 1 && past_maximum_results(&error) => {
+                self.done = true;
+                return Ok(());
+            }
+            page => page?,
+        };
+        let count = page.articles.len();
+        self.articles.extend(page.articles);
+        self.page += 1;
+        self.done = count < self.page_size as usize || self.articles.len() >= page.total_results;
+        Ok(())
+    }
+}
+
+/// NewsAPI refused a page past the most results the plan may page through
+#[cfg(any(feature = "async", feature = "blocking"))]
+fn past_maximum_results(error: &NewsApiError) -> bool {
+    error
+        .api_error()
+        .is_some_and(|error| error.code == "maximumResultsReached")
+}
+
+fn published_at(article: &Article) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&article.published_at)
+        .ok()
+        .map(|published| published.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::article::Articles;
+    use crate::testing::articles_page;
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    use crate::{testing::scripted_client, transport::HttpResponse};
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    use std::sync::{Arc, Mutex};
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    use std::time::Duration;
+
+    /// An everything response listing `(url, publishedAt seconds past noon)`, newest first
+    fn page(articles: &[(&str, u32)], total: usize) -> String {
+        let articles: Vec<_> = articles
+            .iter()
+            .map(|(url, second)| (*url, format!("2022-06-18T12:00:0{second}Z")))
+            .collect();
+        articles_page(&articles, total)
+    }
+
+    fn urls(batch: &SyncBatch) -> Vec<&str> {
+        batch.articles.iter().map(|a| a.url.as_str()).collect()
+    }
+
+    fn noon_plus(second: u32) -> DateTime<Utc> {
+        format!("2022-06-18T12:00:0{second}Z").parse().unwrap()
+    }
+
+    #[test]
+    fn overlap() {
+        let mut state = SyncState::new();
+        let articles: Articles =
+            serde_json::from_str(&page(&[("c", 2), ("b", 1), ("a", 1)], 3)).unwrap();
+        let batch = state.batch("rust", articles.articles);
+        assert_eq!(urls(&batch), ["b", "a", "c"]);
+        assert!(state.watermark("rust").is_none());
+
+        state.acknowledge(&batch);
+        assert_eq!(
+            state.watermark("rust"),
+            Some(&Watermark {
+                published_at: noon_plus(2),
+                urls: vec!["c".to_owned()],
+            })
+        );
+
+        // the page for `from` = the watermark repeats "c", and "d" shares its second
+        let articles: Articles =
+            serde_json::from_str(&page(&[("e", 3), ("d", 2), ("c", 2)], 5)).unwrap();
+        let newer = state.batch("rust", articles.articles);
+        assert_eq!(urls(&newer), ["d", "e"]);
+
+        // acknowledging the older batch again changes nothing
+        state.acknowledge(&newer);
+        state.acknowledge(&batch);
+        assert_eq!(state.watermark("rust").unwrap().published_at, noon_plus(3));
+
+        let stored = serde_json::to_string(&state).unwrap();
+        assert_eq!(serde_json::from_str::<SyncState>(&stored).unwrap(), state);
+    }
+
+    #[test]
+    fn overlapping_pages() {
+        // "c" moved from the end of the first page to the start of the second
+        let first: Articles = serde_json::from_str(&page(&[("d", 3), ("c", 2)], 4)).unwrap();
+        let second: Articles = serde_json::from_str(&page(&[("c", 2), ("b", 1)], 4)).unwrap();
+        let fetched = first.articles.into_iter().chain(second.articles).collect();
+
+        let batch = SyncState::new().batch("rust", fetched);
+        assert_eq!(urls(&batch), ["b", "c", "d"]);
+        assert_eq!(batch.watermark.unwrap().urls, ["d"]);
+
+        let fetched = (0..2)
+            .flat_map(|_| {
+                serde_json::from_str::<Articles>(&page(&[("b", 1), ("a", 1)], 2))
+                    .unwrap()
+                    .articles
+            })
+            .collect();
+        let batch = SyncState::new().batch("rust", fetched);
+        assert_eq!(urls(&batch), ["b", "a"]);
+        assert_eq!(batch.watermark.unwrap().urls, ["b", "a"]);
+    }
+
+    /// A client answering each request with the next of `pages`, then failing, and the URLs
+    /// it was sent
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn client_for(pages: Vec<String>) -> (NewsAPIClient, Arc<Mutex<Vec<String>>>) {
+        let mut responses: Vec<_> = pages
+            .iter()
+            .map(|page| HttpResponse::new(200, page))
+            .collect();
+        responses.push(HttpResponse::new(500, "down"));
+        scripted_client(responses)
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn fetch(
+        state: &SyncState,
+        api: &NewsAPIClient,
+        spec: &EverythingSpec,
+    ) -> Result<SyncBatch, NewsApiError> {
+        #[cfg(feature = "async")]
+        return crate::execute::block_on(state.fetch_async(api, "rust", spec));
+        #[cfg(not(feature = "async"))]
+        return state.fetch_sync(api, "rust", spec);
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn fetch_since_watermark() {
+        let (api, sent) = client_for(vec![
+            page(&[("b", 1), ("a", 0)], 2),
+            page(&[("c", 2), ("b", 1)], 2),
+        ]);
+        let spec = EverythingSpec {
+            query: Some("rust".to_owned()),
+            sort_by: Some(SortMethod::Popularity),
+            ..EverythingSpec::default()
+        };
+
+        let mut state = SyncState::new();
+        let batch = fetch(&state, &api, &spec).unwrap();
+        assert_eq!(urls(&batch), ["a", "b"]);
+        state.acknowledge(&batch);
+
+        let batch = fetch(&state, &api, &spec).unwrap();
+        assert_eq!(urls(&batch), ["c"]);
+
+        // newest first whatever the spec asks for, so the pages line up with the watermark
+        let sent = sent.lock().unwrap();
+        assert_eq!(
+            sent[0],
+            "https://newsapi.org/v2/everything?q=rust&sortBy=publishedAt&pageSize=100&page=1"
+        );
+        assert!(
+            sent[1].contains("&from=2022-06-18T12:00:01&sortBy=publishedAt&"),
+            "{}",
+            sent[1]
+        );
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn fetch_every_page() {
+        let spec = EverythingSpec {
+            query: Some("rust".to_owned()),
+            page_size: Some(2),
+            ..EverythingSpec::default()
+        };
+
+        let (api, sent) = client_for(vec![
+            page(&[("e", 4), ("d", 3)], 5),
+            page(&[("c", 2), ("b", 1)], 5),
+            page(&[("a", 0)], 5),
+        ]);
+        let batch = fetch(&SyncState::new(), &api, &spec).unwrap();
+        assert_eq!(urls(&batch), ["a", "b", "c", "d", "e"]);
+        assert_eq!(sent.lock().unwrap().len(), 3);
+        assert!(sent.lock().unwrap()[2].ends_with("&pageSize=2&page=3"));
+
+        // a later page failing fails the fetch rather than skipping what it held
+        let (api, _) = client_for(vec![page(&[("e", 4), ("d", 3)], 5)]);
+        assert!(fetch(&SyncState::new(), &api, &spec).is_err());
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn fetch_until_maximum_results() {
+        let spec = EverythingSpec {
+            query: Some("rust".to_owned()),
+            page_size: Some(2),
+            ..EverythingSpec::default()
+        };
+        let maximum = r#"{"status": "error", "code": "maximumResultsReached", "message": "max"}"#;
+        let (api, sent) = scripted_client(vec![
+            HttpResponse::new(200, &page(&[("e", 4), ("d", 3)], 500)),
+            HttpResponse::new(426, maximum),
+        ]);
+
+        // the newest articles the plan may read make up the batch
+        let batch = fetch(&SyncState::new(), &api, &spec).unwrap();
+        assert_eq!(urls(&batch), ["d", "e"]);
+        assert_eq!(sent.lock().unwrap().len(), 2);
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn fetch_checks_page_size() {
+        let (api, sent) = client_for(vec![]);
+        for size in [0, 150] {
+            let spec = EverythingSpec {
+                query: Some("rust".to_owned()),
+                page_size: Some(size),
+                ..EverythingSpec::default()
+            };
+            assert!(matches!(
+                fetch(&SyncState::new(), &api, &spec),
+                Err(NewsApiError::InvalidUrlError { .. })
+            ));
+        }
+        assert!(sent.lock().unwrap().is_empty());
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn deadline_covers_every_page() {
+        let spec = EverythingSpec {
+            query: Some("rust".to_owned()),
+            page_size: Some(1),
+            ..EverythingSpec::default()
+        };
+        let (mut api, sent) = client_for(vec![
+            page(&[("c", 2)], 3),
+            page(&[("b", 1)], 3),
+            page(&[("a", 0)], 3),
+        ]);
+        // each page is well within the deadline, all three together are not
+        api.deadline(Duration::from_millis(50))
+            .after_response(|_, response| {
+                std::thread::sleep(Duration::from_millis(30));
+                Ok(response)
+            });
+
+        assert!(matches!(
+            fetch(&SyncState::new(), &api, &spec),
+            Err(NewsApiError::Timeout)
+        ));
+        assert_eq!(sent.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,561 @@
+use super::error::NewsApiError;
+use super::transport::HttpRequest;
+use chrono::{NaiveDate, Utc};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::fmt::{self, Debug, Display};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::{env, fs};
+
+/// The environment variable `ApiKey::from_env` reads
+pub const API_KEY_ENV: &str = "NEWSAPI_KEY";
+
+/// The query parameter `AuthMode::QueryParameter` puts the key in
+pub const API_KEY_PARAMETER: &str = "apiKey";
+
+/// Headers whose values never appear in `Debug` output
+const SECRET_HEADERS: &[&str] = &["X-Api-Key", "Authorization"];
+
+/// A NewsAPI key. `Debug` and `Display` print `ApiKey(REDACTED)` so the key cannot end up in
+/// logs by accident; use `expose` where the real value is needed.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiKey(String);
+
+impl ApiKey {
+    pub fn new(key: impl Into<String>) -> ApiKey {
+        ApiKey(key.into())
+    }
+
+    /// Read the key from the `NEWSAPI_KEY` environment variable
+    pub fn from_env() -> Result<ApiKey, NewsApiError> {
+        ApiKey::from_env_var(API_KEY_ENV)
+    }
+
+    /// Read the key from the environment variable `name`
+    pub fn from_env_var(name: &str) -> Result<ApiKey, NewsApiError> {
+        let key = env::var(name).map_err(|error| NewsApiError::ApiKeyError {
+            message: format!("{name}: {error}"),
+        })?;
+        ApiKey::non_empty(key.trim(), name)
+    }
+
+    /// Read the key from a file, e.g. a mounted secret, ignoring surrounding whitespace
+    pub fn from_file(path: impl AsRef<Path>) -> Result<ApiKey, NewsApiError> {
+        let path = path.as_ref();
+        let key = fs::read_to_string(path).map_err(|error| NewsApiError::ApiKeyError {
+            message: format!("{}: {error}", path.display()),
+        })?;
+        ApiKey::non_empty(key.trim(), &path.display().to_string())
+    }
+
+    /// Ask `provider`, e.g. a secret manager client, for the key
+    pub fn from_provider<F, E>(provider: F) -> Result<ApiKey, NewsApiError>
+    where
+        F: FnOnce() -> Result<String, E>,
+        E: Display,
+    {
+        let key = provider().map_err(|error| NewsApiError::ApiKeyError {
+            message: error.to_string(),
+        })?;
+        ApiKey::non_empty(key.trim(), "provider")
+    }
+
+    /// The key itself
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    fn non_empty(key: &str, source: &str) -> Result<ApiKey, NewsApiError> {
+        if key.is_empty() {
+            return Err(NewsApiError::ApiKeyError {
+                message: format!("{source}: the key is empty"),
+            });
+        }
+        Ok(ApiKey::new(key))
+    }
+}
+
+impl Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ApiKey(REDACTED)")
+    }
+}
+
+impl Display for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("REDACTED")
+    }
+}
+
+impl From<String> for ApiKey {
+    fn from(key: String) -> ApiKey {
+        ApiKey(key)
+    }
+}
+
+impl From<&str> for ApiKey {
+    fn from(key: &str) -> ApiKey {
+        ApiKey::new(key)
+    }
+}
+
+/// Where requests carry the API key
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMode {
+    /// An `X-Api-Key` header
+    #[default]
+    Header,
+    /// The `apiKey` query parameter, for gateways that strip custom headers. The value is
+    /// redacted from `Debug` output, tracing, errors and response URLs; cache keys and
+    /// fingerprints are built from the URL before the key is added.
+    QueryParameter,
+    /// An `Authorization: Bearer` header
+    Bearer,
+}
+
+impl AuthMode {
+    /// Add `key` to `request`
+    pub(crate) fn apply(&self, mut request: HttpRequest, key: &ApiKey) -> HttpRequest {
+        match self {
+            AuthMode::Header => request.header("X-Api-Key", key.expose()),
+            AuthMode::Bearer => {
+                request.header("Authorization", &format!("Bearer {}", key.expose()))
+            }
+            AuthMode::QueryParameter => {
+                let separator = if request.url.contains('?') { '&' } else { '?' };
+                request.url.push(separator);
+                request.url.push_str(API_KEY_PARAMETER);
+                request.url.push('=');
+                request
+                    .url
+                    .extend(utf8_percent_encode(key.expose(), NON_ALPHANUMERIC));
+                request
+            }
+        }
+    }
+}
+
+/// `url` with the value of the `apiKey` query parameter replaced by `REDACTED`
+pub(crate) fn redact_url(url: &str) -> String {
+    let (base, query) = match url.split_once('?') {
+        Some(parts) => parts,
+        None => return url.to_owned(),
+    };
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((API_KEY_PARAMETER, _)) => format!("{API_KEY_PARAMETER}=REDACTED"),
+            _ => pair.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{base}?{query}")
+}
+
+/// `value`, or `REDACTED` if the header called `name` carries a key
+pub(crate) fn redact_header<'a>(name: &str, value: &'a str) -> &'a str {
+    if is_secret_header(name) {
+        "REDACTED"
+    } else {
+        value
+    }
+}
+
+/// True if the header called `name` can carry a key
+pub(crate) fn is_secret_header(name: &str) -> bool {
+    SECRET_HEADERS
+        .iter()
+        .any(|secret| secret.eq_ignore_ascii_case(name))
+}
+
+/// How a `KeyPool` picks the key for the next request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// Each available key in turn
+    RoundRobin,
+    /// The available key with the most of today's quota left, or the least used one when no
+    /// daily quota was given
+    MostRemaining,
+}
+
+/// Several keys shared by one client. A request that fails because of its key
+/// (`Unauthorized`, `rateLimited` or `apiKeyExhausted`) is retried with the next key, and the
+/// failed key is benched: until midnight UTC when its quota is exhausted, otherwise for
+/// `bench_for`.
+#[derive(Debug)]
+pub struct KeyPool {
+    rotation: Rotation,
+    daily_quota: Option<u32>,
+    bench_for: Duration,
+    state: Mutex<PoolState>,
+}
+
+#[derive(Debug)]
+struct PoolState {
+    keys: Vec<PooledKey>,
+    /// Where round robin looks for the next key
+    cursor: usize,
+}
+
+#[derive(Debug)]
+struct PooledKey {
+    label: String,
+    key: ApiKey,
+    requests: u64,
+    failures: u64,
+    day: Option<NaiveDate>,
+    used_today: u32,
+    /// NewsAPI answered `apiKeyExhausted` for the key today
+    exhausted_today: bool,
+    benched_until: Option<Instant>,
+}
+
+impl PooledKey {
+    fn roll_over(&mut self, today: NaiveDate) {
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.used_today = 0;
+            self.exhausted_today = false;
+        }
+    }
+
+    fn benched_for(&self, now: Instant) -> Option<Duration> {
+        self.benched_until
+            .filter(|until| *until > now)
+            .map(|until| until - now)
+    }
+}
+
+/// How much one key in a `KeyPool` has been used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyUsage {
+    pub label: String,
+    /// Requests sent with the key
+    pub requests: u64,
+    /// Requests that failed because of the key
+    pub failures: u64,
+    pub used_today: u32,
+    /// Only known when the pool was given a daily quota
+    pub remaining_today: Option<u32>,
+    /// How much longer the key sits out, if it is benched
+    pub benched_for: Option<Duration>,
+}
+
+impl Default for KeyPool {
+    fn default() -> KeyPool {
+        KeyPool::new()
+    }
+}
+
+impl KeyPool {
+    /// An empty round robin pool benching failed keys for a minute
+    pub fn new() -> KeyPool {
+        KeyPool {
+            rotation: Rotation::RoundRobin,
+            daily_quota: None,
+            bench_for: Duration::from_secs(60),
+            state: Mutex::new(PoolState {
+                keys: vec![],
+                cursor: 0,
+            }),
+        }
+    }
+
+    /// Add `key`, reported in `usage` as `label`
+    pub fn key(self, label: &str, key: impl Into<ApiKey>) -> KeyPool {
+        self.state.lock().unwrap().keys.push(PooledKey {
+            label: label.to_owned(),
+            key: key.into(),
+            requests: 0,
+            failures: 0,
+            day: None,
+            used_today: 0,
+            exhausted_today: false,
+            benched_until: None,
+        });
+        self
+    }
+
+    pub fn rotation(mut self, rotation: Rotation) -> KeyPool {
+        self.rotation = rotation;
+        self
+    }
+
+    /// The number of requests each key's plan allows per day
+    pub fn daily_quota(mut self, limit: u32) -> KeyPool {
+        self.daily_quota = Some(limit);
+        self
+    }
+
+    /// How long a rate limited or rejected key sits out
+    pub fn bench_for(mut self, duration: Duration) -> KeyPool {
+        self.bench_for = duration;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn usage(&self) -> Vec<KeyUsage> {
+        let now = Instant::now();
+        let today = Utc::now().date_naive();
+        let mut state = self.state.lock().unwrap();
+        state
+            .keys
+            .iter_mut()
+            .map(|key| {
+                key.roll_over(today);
+                KeyUsage {
+                    label: key.label.clone(),
+                    requests: key.requests,
+                    failures: key.failures,
+                    used_today: key.used_today,
+                    remaining_today: self
+                        .daily_quota
+                        .map(|limit| limit.saturating_sub(key.used_today)),
+                    benched_for: key.benched_for(now),
+                }
+            })
+            .collect()
+    }
+
+    /// Pick the key for the next request and count it as used
+    pub(crate) fn acquire(&self) -> Result<(usize, ApiKey), NewsApiError> {
+        let now = Instant::now();
+        let today = Utc::now().date_naive();
+        let mut state = self.state.lock().unwrap();
+        let len = state.keys.len();
+        let daily_quota = self.daily_quota;
+
+        let usable = |key: &PooledKey| {
+            key.benched_for(now).is_none() && daily_quota.is_none_or(|limit| key.used_today < limit)
+        };
+        for key in &mut state.keys {
+            key.roll_over(today);
+        }
+
+        let index = match self.rotation {
+            Rotation::RoundRobin => (0..len)
+                .map(|offset| (state.cursor + offset) % len)
+                .find(|index| usable(&state.keys[*index])),
+            Rotation::MostRemaining => (0..len)
+                .filter(|index| usable(&state.keys[*index]))
+                .min_by_key(|index| state.keys[*index].used_today),
+        };
+        let index = index.ok_or_else(|| NewsApiError::ApiKeyError {
+            message: "every key in the pool is benched or out of quota".into(),
+        })?;
+
+        state.cursor = (index + 1) % len;
+        let key = &mut state.keys[index];
+        key.requests += 1;
+        key.used_today += 1;
+        Ok((index, key.key.clone()))
+    }
+
+    /// The label of the key at `index`
+    pub(crate) fn label(&self, index: usize) -> String {
+        self.state.lock().unwrap().keys[index].label.clone()
+    }
+
+    /// Bench the key at `index` if `error` was caused by it, returning whether another key
+    /// might do better
+    pub(crate) fn fail_over(&self, index: usize, error: &NewsApiError) -> bool {
+        let exhausted = error.exhausts_quota();
+        let bench_for = match error {
+            NewsApiError::TooManyRequests { .. } if exhausted => until_midnight_utc(),
+            NewsApiError::TooManyRequests { .. } | NewsApiError::Unauthorized { .. } => {
+                self.bench_for
+            }
+            _ => return false,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        let key = &mut state.keys[index];
+        key.failures += 1;
+        key.benched_until = Some(Instant::now() + bench_for);
+        if exhausted {
+            key.roll_over(Utc::now().date_naive());
+            key.exhausted_today = true;
+        }
+        true
+    }
+
+    /// True once NewsAPI has answered `apiKeyExhausted` for every key today
+    pub(crate) fn exhausted(&self) -> bool {
+        let today = Utc::now().date_naive();
+        let mut state = self.state.lock().unwrap();
+        state.keys.iter_mut().all(|key| {
+            key.roll_over(today);
+            key.exhausted_today
+        })
+    }
+}
+
+/// How long until the daily quota resets at midnight UTC
+pub(crate) fn until_midnight_utc() -> Duration {
+    let now = Utc::now();
+    let midnight = (now.date_naive() + chrono::Duration::days(1))
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc();
+    (midnight - now).to_std().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted() {
+        let key = ApiKey::new("0123456789abcdef");
+        assert_eq!(format!("{key:?}"), "ApiKey(REDACTED)");
+        assert_eq!(key.to_string(), "REDACTED");
+        assert_eq!(key.expose(), "0123456789abcdef");
+    }
+
+    #[test]
+    fn sources() {
+        env::set_var("NEWSAPI_TEST_KEY", " from-env\n");
+        assert_eq!(
+            ApiKey::from_env_var("NEWSAPI_TEST_KEY").unwrap().expose(),
+            "from-env"
+        );
+        assert!(ApiKey::from_env_var("NEWSAPI_TEST_KEY_UNSET").is_err());
+
+        let path = env::temp_dir().join(format!("newsapi-key-{}", std::process::id()));
+        fs::write(&path, "from-file\n").unwrap();
+        assert_eq!(ApiKey::from_file(&path).unwrap().expose(), "from-file");
+        fs::write(&path, "\n").unwrap();
+        assert!(matches!(
+            ApiKey::from_file(&path),
+            Err(NewsApiError::ApiKeyError { .. })
+        ));
+        fs::remove_file(&path).unwrap();
+
+        let key = ApiKey::from_provider(|| Ok::<_, String>("from-provider".into())).unwrap();
+        assert_eq!(key.expose(), "from-provider");
+        let error = ApiKey::from_provider(|| Err::<String, _>("vault sealed")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unable to load the API key: vault sealed"
+        );
+    }
+
+    #[test]
+    fn auth_modes() {
+        let key = ApiKey::new("s3cret/+");
+        let request = || HttpRequest::new("https://newsapi.org/v2/sources?language=en");
+
+        let header = AuthMode::Header.apply(request(), &key);
+        assert_eq!(header.header_value("x-api-key"), Some("s3cret/+"));
+
+        let bearer = AuthMode::Bearer.apply(request(), &key);
+        assert_eq!(
+            bearer.header_value("authorization"),
+            Some("Bearer s3cret/+")
+        );
+
+        let query = AuthMode::QueryParameter.apply(request(), &key);
+        assert_eq!(
+            query.url,
+            "https://newsapi.org/v2/sources?language=en&apiKey=s3cret%2F%2B"
+        );
+        let no_query = AuthMode::QueryParameter.apply(HttpRequest::new("https://x/sources"), &key);
+        assert_eq!(no_query.url, "https://x/sources?apiKey=s3cret%2F%2B");
+
+        for request in [header, bearer, query] {
+            assert!(!format!("{request:?}").contains("s3cret"));
+        }
+        assert_eq!(
+            redact_url("https://x/everything?q=a&apiKey=123"),
+            "https://x/everything?q=a&apiKey=REDACTED"
+        );
+    }
+
+    fn rate_limited() -> NewsApiError {
+        NewsApiError::TooManyRequests {
+            code: 429,
+            message: r#"{"status": "error", "code": "rateLimited", "message": "slow down"}"#.into(),
+        }
+    }
+
+    #[test]
+    fn round_robin() {
+        let pool = KeyPool::new()
+            .key("a", "key-a")
+            .key("b", "key-b")
+            .key("c", "key-c");
+        let picked: Vec<_> = (0..4).map(|_| pool.acquire().unwrap().1).collect();
+        assert_eq!(
+            picked,
+            ["key-a", "key-b", "key-c", "key-a"].map(ApiKey::from)
+        );
+
+        assert!(pool.fail_over(1, &rate_limited()));
+        assert_eq!(pool.acquire().unwrap().1.expose(), "key-c");
+        assert_eq!(pool.acquire().unwrap().1.expose(), "key-a");
+        assert_eq!(pool.acquire().unwrap().1.expose(), "key-c");
+
+        let bad_request = NewsApiError::BadRequest {
+            code: 400,
+            message: "parameterInvalid".into(),
+        };
+        assert!(!pool.fail_over(0, &bad_request));
+
+        let usage = pool.usage();
+        assert_eq!(usage[1].failures, 1);
+        assert!(usage[1].benched_for.is_some());
+        assert_eq!(usage[0].requests, 3);
+        assert_eq!(usage[0].remaining_today, None);
+    }
+
+    #[test]
+    fn most_remaining() {
+        let pool = KeyPool::new()
+            .key("a", "key-a")
+            .key("b", "key-b")
+            .rotation(Rotation::MostRemaining)
+            .daily_quota(2);
+
+        assert_eq!(pool.acquire().unwrap().0, 0);
+        assert_eq!(pool.acquire().unwrap().0, 1);
+        assert_eq!(pool.acquire().unwrap().0, 0);
+        assert_eq!(pool.acquire().unwrap().0, 1);
+        assert!(matches!(
+            pool.acquire(),
+            Err(NewsApiError::ApiKeyError { .. })
+        ));
+        assert_eq!(pool.usage()[0].remaining_today, Some(0));
+    }
+
+    #[test]
+    fn exhausted_until_midnight() {
+        let pool = KeyPool::new().key("a", "key-a").bench_for(Duration::ZERO);
+        let exhausted = NewsApiError::TooManyRequests {
+            code: 429,
+            message: r#"{"status": "error", "code": "apiKeyExhausted", "message": "done"}"#.into(),
+        };
+
+        pool.acquire().unwrap();
+        assert!(pool.fail_over(0, &exhausted));
+        assert!(pool.acquire().is_err());
+        assert!(pool.exhausted());
+
+        // a key that is only rate limited still has requests left today
+        let pool = KeyPool::new().key("a", "key-a").key("b", "key-b");
+        let rate_limited = NewsApiError::TooManyRequests {
+            code: 429,
+            message: r#"{"status": "error", "code": "rateLimited", "message": "slow"}"#.into(),
+        };
+        pool.fail_over(0, &exhausted);
+        pool.fail_over(1, &rate_limited);
+        assert!(!pool.exhausted());
+    }
+}
@@ -1,4 +1,35 @@
+// Request preparation and response handling are only reachable through `send_async` or
+// `send_sync`, so they go unused when both features are turned off.
+#![cfg_attr(not(any(feature = "async", feature = "blocking")), allow(dead_code))]
+
+#[cfg(test)]
+#[macro_use]
+mod testing;
+
 pub mod api;
+pub mod batch;
+pub mod cancel;
+pub mod circuit;
 pub mod constants;
+pub mod endpoint;
 pub mod error;
+mod execute;
+pub mod explain;
+pub mod fingerprint;
+pub mod incremental;
+pub mod key;
+pub mod metrics;
+pub mod middleware;
 pub mod payload;
+pub mod request;
+pub mod response;
+#[cfg(feature = "async")]
+pub mod single_flight;
+pub mod spec;
+pub mod stale;
+#[cfg(feature = "async")]
+mod timer;
+mod trace;
+pub mod transport;
+#[cfg(feature = "async")]
+pub mod watch;
@@ -0,0 +1,342 @@
+use super::error::NewsApiError;
+use chrono::{NaiveDate, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the request latency histogram buckets
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Counters describing how a client uses NewsAPI. Share one between clients with
+/// `NewsAPIClient::metrics` and scrape it with `prometheus`.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    daily_quota: Option<u32>,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    /// Keyed by endpoint and outcome, `ok` or `error`
+    requests: BTreeMap<(String, &'static str), u64>,
+    latency: BTreeMap<String, Histogram>,
+    /// Keyed by `NewsApiError` variant
+    errors: BTreeMap<&'static str, u64>,
+    stale_hits: u64,
+    stale_misses: u64,
+    /// Requests answered with an identical in-flight request's response
+    coalesced: u64,
+    quota: Quota,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; LATENCY_BUCKETS.len()];
+        }
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// Requests sent during the current UTC day. NewsAPI does not report the remaining quota, so
+/// it is worked out from the plan's daily limit and reset at midnight UTC.
+#[derive(Debug, Default)]
+struct Quota {
+    day: Option<NaiveDate>,
+    used: u32,
+    /// NewsAPI answered `apiKeyExhausted` today with no key left to try
+    exhausted: bool,
+}
+
+impl Quota {
+    fn roll_over(&mut self, today: NaiveDate) {
+        if self.day != Some(today) {
+            *self = Quota {
+                day: Some(today),
+                ..Quota::default()
+            };
+        }
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// Also track how much of a plan allowing `limit` requests a day is left
+    pub fn with_daily_quota(limit: u32) -> Metrics {
+        Metrics {
+            daily_quota: Some(limit),
+            ..Metrics::default()
+        }
+    }
+
+    /// Requests made to `endpoint` (e.g. `top-headlines`) with the given outcome, `ok` or `error`
+    pub fn requests(&self, endpoint: &str, outcome: &str) -> u64 {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .requests
+            .iter()
+            .filter(|((e, o), _)| e == endpoint && *o == outcome)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Failed requests by `NewsApiError` variant, e.g. `TooManyRequests`
+    pub fn errors(&self, kind: &str) -> u64 {
+        let inner = self.inner.lock().unwrap();
+        inner.errors.get(kind).copied().unwrap_or(0)
+    }
+
+    /// How many failed requests the stale cache could answer, or `None` if it was never asked
+    pub fn stale_cache_hit_ratio(&self) -> Option<f64> {
+        let inner = self.inner.lock().unwrap();
+        let lookups = inner.stale_hits + inner.stale_misses;
+        (lookups > 0).then(|| inner.stale_hits as f64 / lookups as f64)
+    }
+
+    /// Requests that shared an identical in-flight request's call to NewsAPI instead of making
+    /// their own, see `NewsAPIClient::single_flight`
+    pub fn coalesced_requests(&self) -> u64 {
+        self.inner.lock().unwrap().coalesced
+    }
+
+    /// Requests left today, when a daily quota was given
+    pub fn quota_remaining(&self) -> Option<u32> {
+        let limit = self.daily_quota?;
+        let mut inner = self.inner.lock().unwrap();
+        inner.quota.roll_over(Utc::now().date_naive());
+        if inner.quota.exhausted {
+            return Some(0);
+        }
+        Some(limit.saturating_sub(inner.quota.used))
+    }
+
+    /// Render everything in the Prometheus text exposition format
+    pub fn prometheus(&self) -> String {
+        let remaining = self.quota_remaining();
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str("# HELP newsapi_requests_total Requests sent to NewsAPI.\n");
+        out.push_str("# TYPE newsapi_requests_total counter\n");
+        for ((endpoint, outcome), count) in &inner.requests {
+            let _ = writeln!(
+                out,
+                "newsapi_requests_total{{endpoint=\"{}\",outcome=\"{}\"}} {}",
+                escape(endpoint),
+                outcome,
+                count
+            );
+        }
+
+        out.push_str("# HELP newsapi_request_duration_seconds Time taken by NewsAPI requests.\n");
+        out.push_str("# TYPE newsapi_request_duration_seconds histogram\n");
+        for (endpoint, histogram) in &inner.latency {
+            let endpoint = escape(endpoint);
+            for (bucket, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(
+                    out,
+                    "newsapi_request_duration_seconds_bucket{{endpoint=\"{}\",le=\"{}\"}} {}",
+                    endpoint, bound, bucket
+                );
+            }
+            let _ = writeln!(
+                out,
+                "newsapi_request_duration_seconds_bucket{{endpoint=\"{endpoint}\",le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "newsapi_request_duration_seconds_sum{{endpoint=\"{endpoint}\"}} {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "newsapi_request_duration_seconds_count{{endpoint=\"{endpoint}\"}} {}",
+                histogram.count
+            );
+        }
+
+        out.push_str("# HELP newsapi_errors_total Failed requests by error kind.\n");
+        out.push_str("# TYPE newsapi_errors_total counter\n");
+        for (kind, count) in &inner.errors {
+            let _ = writeln!(out, "newsapi_errors_total{{kind=\"{kind}\"}} {count}");
+        }
+
+        out.push_str(
+            "# HELP newsapi_stale_cache_lookups_total Failed requests looked up in the stale cache.\n",
+        );
+        out.push_str("# TYPE newsapi_stale_cache_lookups_total counter\n");
+        let _ = writeln!(
+            out,
+            "newsapi_stale_cache_lookups_total{{result=\"hit\"}} {}",
+            inner.stale_hits
+        );
+        let _ = writeln!(
+            out,
+            "newsapi_stale_cache_lookups_total{{result=\"miss\"}} {}",
+            inner.stale_misses
+        );
+
+        out.push_str(
+            "# HELP newsapi_coalesced_requests_total Requests that shared an identical in-flight request's call.\n",
+        );
+        out.push_str("# TYPE newsapi_coalesced_requests_total counter\n");
+        let _ = writeln!(out, "newsapi_coalesced_requests_total {}", inner.coalesced);
+
+        if let Some(remaining) = remaining {
+            out.push_str("# HELP newsapi_quota_remaining Requests left in today's quota.\n");
+            out.push_str("# TYPE newsapi_quota_remaining gauge\n");
+            let _ = writeln!(out, "newsapi_quota_remaining {remaining}");
+        }
+
+        out
+    }
+
+    /// A request to `endpoint` finished after `elapsed`
+    pub(crate) fn record_request<T>(
+        &self,
+        endpoint: &str,
+        result: &Result<T, NewsApiError>,
+        elapsed: Duration,
+    ) {
+        let mut inner = self.inner.lock().unwrap();
+        let outcome = if result.is_ok() { "ok" } else { "error" };
+        *inner
+            .requests
+            .entry((endpoint.to_owned(), outcome))
+            .or_default() += 1;
+        inner
+            .latency
+            .entry(endpoint.to_owned())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+
+        if let Err(error) = result {
+            *inner.errors.entry(error.kind()).or_default() += 1;
+        }
+    }
+
+    /// NewsAPI answered `apiKeyExhausted` and no other key has requests left today
+    pub(crate) fn record_quota_exhausted(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.quota.roll_over(Utc::now().date_naive());
+        inner.quota.exhausted = true;
+    }
+
+    /// A request went out to NewsAPI and counts against the daily quota
+    pub(crate) fn record_quota_use(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.quota.roll_over(Utc::now().date_naive());
+        inner.quota.used += 1;
+    }
+
+    pub(crate) fn record_stale_lookup(&self, hit: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if hit {
+            inner.stale_hits += 1;
+        } else {
+            inner.stale_misses += 1;
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn record_coalesced(&self) {
+        self.inner.lock().unwrap().coalesced += 1;
+    }
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prometheus() {
+        let metrics = Metrics::with_daily_quota(100);
+        let ok: Result<(), NewsApiError> = Ok(());
+        let limited: Result<(), NewsApiError> = Err(NewsApiError::TooManyRequests {
+            code: 429,
+            message: r#"{"status": "error", "code": "rateLimited", "message": "slow down"}"#.into(),
+        });
+
+        metrics.record_quota_use();
+        metrics.record_request("sources", &ok, Duration::from_millis(70));
+        metrics.record_quota_use();
+        metrics.record_request("sources", &limited, Duration::from_secs(3));
+        metrics.record_stale_lookup(true);
+        #[cfg(feature = "async")]
+        metrics.record_coalesced();
+
+        assert_eq!(metrics.requests("sources", "ok"), 1);
+        assert_eq!(metrics.requests("sources", "error"), 1);
+        assert_eq!(metrics.errors("TooManyRequests"), 1);
+        assert_eq!(metrics.stale_cache_hit_ratio(), Some(1.0));
+        assert_eq!(metrics.quota_remaining(), Some(98));
+
+        let text = metrics.prometheus();
+        for line in [
+            "newsapi_requests_total{endpoint=\"sources\",outcome=\"ok\"} 1",
+            "newsapi_requests_total{endpoint=\"sources\",outcome=\"error\"} 1",
+            "newsapi_request_duration_seconds_bucket{endpoint=\"sources\",le=\"0.05\"} 0",
+            "newsapi_request_duration_seconds_bucket{endpoint=\"sources\",le=\"0.1\"} 1",
+            "newsapi_request_duration_seconds_bucket{endpoint=\"sources\",le=\"5\"} 2",
+            "newsapi_request_duration_seconds_bucket{endpoint=\"sources\",le=\"+Inf\"} 2",
+            "newsapi_request_duration_seconds_count{endpoint=\"sources\"} 2",
+            "newsapi_errors_total{kind=\"TooManyRequests\"} 1",
+            "newsapi_stale_cache_lookups_total{result=\"hit\"} 1",
+            "newsapi_stale_cache_lookups_total{result=\"miss\"} 0",
+            #[cfg(feature = "async")]
+            "newsapi_coalesced_requests_total 1",
+            "newsapi_quota_remaining 98",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {} in\n{}",
+                line,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn quota_exhausted() {
+        let metrics = Metrics::with_daily_quota(100);
+        let exhausted: Result<(), NewsApiError> = Err(NewsApiError::TooManyRequests {
+            code: 429,
+            message: r#"{"status": "error", "code": "apiKeyExhausted", "message": "done"}"#.into(),
+        });
+        // a single answer does not say whether another key has requests left
+        metrics.record_request("everything", &exhausted, Duration::ZERO);
+        assert_eq!(metrics.quota_remaining(), Some(100));
+        metrics.record_quota_exhausted();
+        assert_eq!(metrics.quota_remaining(), Some(0));
+
+        assert_eq!(Metrics::new().quota_remaining(), None);
+        assert!(!Metrics::new()
+            .prometheus()
+            .contains("newsapi_quota_remaining"));
+    }
+}
@@ -0,0 +1,142 @@
+use super::error::NewsApiError;
+use super::transport::{HttpRequest, HttpResponse};
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+/// What a `before_request` hook wants to happen next
+#[derive(Debug)]
+pub enum Flow {
+    /// Send the (possibly modified) request to the transport
+    Continue,
+    /// Skip the transport and carry on as if it had returned this response
+    Respond(HttpResponse),
+}
+
+/// Hooks run around every request, in the order they were added to the client, for both
+/// `send_async` and `send_sync`. Returning an error from either hook aborts the request with
+/// that error.
+pub trait Middleware: Debug + Send + Sync {
+    /// Called before the request goes to the transport. May change the URL and headers, or
+    /// short-circuit with a response of its own; later `before_request` hooks are then skipped.
+    fn before_request(&self, _request: &mut HttpRequest) -> Result<Flow, NewsApiError> {
+        Ok(Flow::Continue)
+    }
+
+    /// Called with whatever the transport (or a short-circuiting hook) returned, before the
+    /// status is checked. May inspect it or return a different one.
+    fn after_response(
+        &self,
+        _request: &HttpRequest,
+        response: HttpResponse,
+    ) -> Result<HttpResponse, NewsApiError> {
+        Ok(response)
+    }
+}
+
+/// A `before_request` hook made from a closure, see `NewsAPIClient::before_request`
+pub struct BeforeRequest<F>(pub F);
+
+impl<F> Debug for BeforeRequest<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("BeforeRequest")
+    }
+}
+
+impl<F> Middleware for BeforeRequest<F>
+where
+    F: Fn(&mut HttpRequest) -> Result<Flow, NewsApiError> + Send + Sync,
+{
+    fn before_request(&self, request: &mut HttpRequest) -> Result<Flow, NewsApiError> {
+        (self.0)(request)
+    }
+}
+
+/// An `after_response` hook made from a closure, see `NewsAPIClient::after_response`
+pub struct AfterResponse<F>(pub F);
+
+impl<F> Debug for AfterResponse<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AfterResponse")
+    }
+}
+
+impl<F> Middleware for AfterResponse<F>
+where
+    F: Fn(&HttpRequest, HttpResponse) -> Result<HttpResponse, NewsApiError> + Send + Sync,
+{
+    fn after_response(
+        &self,
+        request: &HttpRequest,
+        response: HttpResponse,
+    ) -> Result<HttpResponse, NewsApiError> {
+        (self.0)(request, response)
+    }
+}
+
+/// Run every `before_request` hook in order, stopping at the first that short-circuits
+pub(crate) fn before_request(
+    chain: &[Arc<dyn Middleware>],
+    request: &mut HttpRequest,
+) -> Result<Option<HttpResponse>, NewsApiError> {
+    for middleware in chain {
+        if let Flow::Respond(response) = middleware.before_request(request)? {
+            return Ok(Some(response));
+        }
+    }
+    Ok(None)
+}
+
+/// Run every `after_response` hook in order
+pub(crate) fn after_response(
+    chain: &[Arc<dyn Middleware>],
+    request: &HttpRequest,
+    mut response: HttpResponse,
+) -> Result<HttpResponse, NewsApiError> {
+    for middleware in chain {
+        response = middleware.after_response(request, response)?;
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_circuit() {
+        let chain: Vec<Arc<dyn Middleware>> = vec![
+            Arc::new(BeforeRequest(|request: &mut HttpRequest| {
+                request.headers.push(("X-First".into(), "1".into()));
+                Ok(Flow::Respond(HttpResponse::new(200, "{}")))
+            })),
+            Arc::new(BeforeRequest(
+                |_: &mut HttpRequest| -> Result<Flow, NewsApiError> {
+                    panic!("skipped after a short-circuit")
+                },
+            )),
+        ];
+
+        let mut request = HttpRequest::new("https://newsapi.org/v2/sources");
+        let response = before_request(&chain, &mut request).unwrap();
+        assert_eq!(response.unwrap().text(), "{}");
+        assert_eq!(request.header_value("x-first"), Some("1"));
+    }
+
+    #[test]
+    fn after_response_in_order() {
+        let chain: Vec<Arc<dyn Middleware>> = vec![
+            Arc::new(AfterResponse(|_: &HttpRequest, response: HttpResponse| {
+                Ok(response.header("X-Seen", "first"))
+            })),
+            Arc::new(AfterResponse(|_: &HttpRequest, response: HttpResponse| {
+                assert_eq!(response.header_value("x-seen"), Some("first"));
+                Ok(HttpResponse::new(503, "replaced"))
+            })),
+        ];
+
+        let request = HttpRequest::new("https://newsapi.org/v2/sources");
+        let response = after_response(&chain, &request, HttpResponse::new(200, "{}")).unwrap();
+        assert_eq!(response.status, 503);
+        assert_eq!(response.text(), "replaced");
+    }
+}
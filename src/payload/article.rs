@@ -1,10 +1,15 @@
 use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Articles {
     pub status: String,
     #[serde(rename = "totalResults")]
     pub total_results: usize,
     pub articles: Vec<Article>,
+    /// Fields NewsAPI sent that this crate does not know about yet
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl Articles {
@@ -17,6 +22,9 @@ impl Articles {
 pub struct ArticleSource {
     pub id: Option<String>,
     pub name: String,
+    /// Fields NewsAPI sent that this crate does not know about yet
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl PartialEq for ArticleSource {
@@ -37,6 +45,9 @@ pub struct Article {
     #[serde(rename = "publishedAt")]
     pub published_at: String,
     pub content: Option<String>,
+    /// Fields NewsAPI sent that this crate does not know about yet
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[cfg(test)]
@@ -63,11 +74,13 @@ mod tests {
         let wired = ArticleSource {
             id: Some("wired".into()),
             name: "Wired".into(),
+            extra: Map::new(),
         };
 
         let engadget = ArticleSource {
             id: Some("engadget".into()),
             name: "Engadget".into(),
+            extra: Map::new(),
         };
 
         let expected_sources = vec![&wired, &engadget];
@@ -89,4 +102,42 @@ mod tests {
         assert_eq!(articles.total_results, 2);
         assert_eq!(articles.articles.len(), 2);
     }
+
+    fn assert_round_trip(contents: &str) {
+        let original: Value = serde_json::from_str(contents).unwrap();
+        let articles: Articles = serde_json::from_str(contents).unwrap();
+        assert_eq!(serde_json::to_value(&articles).unwrap(), original);
+    }
+
+    #[test]
+    fn round_trip() {
+        assert_round_trip(&load_file("resources/example_everything.json"));
+        assert_round_trip(&load_file("resources/example_headlines.json"));
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let contents = r#"{
+            "status": "ok",
+            "totalResults": 1,
+            "nextPage": "abc",
+            "articles": [{
+                "source": {"id": null, "name": "Example", "country": "gb"},
+                "author": null,
+                "title": "Title",
+                "description": null,
+                "url": "https://example.com/story",
+                "urlToImage": null,
+                "publishedAt": "2019-03-01T12:00:00Z",
+                "content": null,
+                "sentiment": {"score": 0.5}
+            }]
+        }"#;
+
+        let articles: Articles = serde_json::from_str(contents).unwrap();
+        assert_eq!(articles.extra["nextPage"], "abc");
+        assert_eq!(articles.articles[0].extra["sentiment"]["score"], 0.5);
+        assert_eq!(articles.articles[0].source.extra["country"], "gb");
+        assert_round_trip(contents);
+    }
 }
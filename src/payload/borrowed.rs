@@ -0,0 +1,102 @@
+use super::article::{Article, ArticleSource};
+use serde_derive::Deserialize;
+use serde_json::Map;
+use std::borrow::Cow;
+
+/// A page of articles borrowing its strings from the response body wherever possible, for
+/// processing large pages without allocating a `String` per field. Fields this crate does not
+/// know about are skipped rather than kept in an `extra` map.
+#[derive(Debug, Deserialize)]
+pub struct ArticlesRef<'a> {
+    #[serde(borrow)]
+    pub status: Cow<'a, str>,
+    #[serde(rename = "totalResults")]
+    pub total_results: usize,
+    #[serde(borrow)]
+    pub articles: Vec<ArticleRef<'a>>,
+}
+
+impl<'a> ArticlesRef<'a> {
+    pub fn from_slice(body: &'a [u8]) -> Result<ArticlesRef<'a>, serde_json::Error> {
+        serde_json::from_slice(body)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct ArticleSourceRef<'a> {
+    #[serde(borrow)]
+    pub id: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArticleRef<'a> {
+    #[serde(borrow)]
+    pub source: ArticleSourceRef<'a>,
+    #[serde(borrow)]
+    pub author: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub title: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub description: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub url: Cow<'a, str>,
+    #[serde(rename = "urlToImage", borrow)]
+    pub url_to_image: Option<Cow<'a, str>>,
+    #[serde(rename = "publishedAt", borrow)]
+    pub published_at: Cow<'a, str>,
+    #[serde(borrow)]
+    pub content: Option<Cow<'a, str>>,
+}
+
+fn owned(value: &Option<Cow<str>>) -> Option<String> {
+    value.as_ref().map(|value| value.clone().into_owned())
+}
+
+impl ArticleRef<'_> {
+    /// Copy into an owned `Article`, e.g. to keep it once the body has been dropped
+    pub fn to_article(&self) -> Article {
+        Article {
+            source: ArticleSource {
+                id: owned(&self.source.id),
+                name: self.source.name.clone().into_owned(),
+                extra: Map::new(),
+            },
+            author: owned(&self.author),
+            title: owned(&self.title),
+            description: owned(&self.description),
+            url: self.url.clone().into_owned(),
+            url_to_image: owned(&self.url_to_image),
+            published_at: self.published_at.clone().into_owned(),
+            content: owned(&self.content),
+            extra: Map::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borrows_from_body() {
+        let contents = include_str!("../../resources/example_everything.json");
+        let articles = ArticlesRef::from_slice(contents.as_bytes()).unwrap();
+
+        assert_eq!(articles.status, "ok");
+        assert_eq!(articles.articles.len(), 2);
+
+        let first = &articles.articles[0];
+        assert!(matches!(first.url, Cow::Borrowed(_)));
+        assert_eq!(first.source.name, "Wired");
+
+        // escaped strings cannot be borrowed and fall back to an owned copy
+        let second = &articles.articles[1];
+        assert!(matches!(second.description, Some(Cow::Owned(_))));
+
+        let article = second.to_article();
+        assert_eq!(article.url, second.url);
+        assert_eq!(article.source.id.as_deref(), Some("engadget"));
+    }
+}
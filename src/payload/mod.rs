@@ -1,2 +1,5 @@
 pub mod article;
+pub mod borrowed;
 pub mod source;
+pub mod status;
+pub mod stream;
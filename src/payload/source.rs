@@ -1,11 +1,15 @@
 use crate::constants::Category;
 
 use serde_derive::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Sources {
     pub status: String,
     pub sources: Vec<Source>,
+    /// Fields NewsAPI sent that this crate does not know about yet
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -17,6 +21,9 @@ pub struct Source {
     pub category: Category,
     pub language: String,
     pub country: String,
+    /// Fields NewsAPI sent that this crate does not know about yet
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[cfg(test)]
@@ -38,4 +45,46 @@ mod tests {
         assert_eq!(sources.status, "ok");
         assert_eq!(sources.sources.len(), 4);
     }
+
+    #[test]
+    fn round_trip() {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/example_sources.json");
+
+        let contents =
+            fs::read_to_string(d.as_path()).expect("Something went wrong reading the file");
+
+        let original: Value = serde_json::from_str(&contents).unwrap();
+        let sources: Sources = serde_json::from_str(&contents).unwrap();
+        assert!(sources.extra.is_empty());
+        assert_eq!(serde_json::to_value(&sources).unwrap(), original);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let contents = r#"{
+            "status": "ok",
+            "sources": [{
+                "id": "abc-news",
+                "name": "ABC News",
+                "description": "Your trusted source for breaking news.",
+                "url": "https://abcnews.go.com",
+                "category": "general",
+                "language": "en",
+                "country": "us",
+                "logo": "https://abcnews.go.com/logo.png"
+            }],
+            "page": 1
+        }"#;
+
+        let sources: Sources = serde_json::from_str(contents).unwrap();
+        assert_eq!(sources.extra["page"], 1);
+        assert_eq!(
+            sources.sources[0].extra["logo"],
+            "https://abcnews.go.com/logo.png"
+        );
+
+        let original: Value = serde_json::from_str(contents).unwrap();
+        assert_eq!(serde_json::to_value(&sources).unwrap(), original);
+    }
 }
@@ -0,0 +1,57 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// The `status` field every NewsAPI response body starts with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseStatus {
+    Ok,
+    Error,
+}
+
+/// Just enough of a body to tell which kind of response it is
+#[derive(Debug, Deserialize)]
+pub(crate) struct StatusProbe {
+    pub status: Option<ResponseStatus>,
+}
+
+/// The body NewsAPI sends when `status` is `"error"`
+/// https://newsapi.org/docs/errors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiError {
+    pub status: ResponseStatus,
+    pub code: String,
+    pub message: String,
+}
+
+impl ApiError {
+    /// The HTTP status NewsAPI pairs with this error code, used when an error body arrives with
+    /// some other status (e.g. a 200 from a rewriting proxy)
+    pub fn http_status(&self) -> u16 {
+        match self.code.as_str() {
+            "apiKeyDisabled" | "apiKeyInvalid" | "apiKeyMissing" => 401,
+            "apiKeyExhausted" | "rateLimited" => 429,
+            "parameterInvalid" | "parametersMissing" | "sourcesTooMany" | "sourceDoesNotExist" => {
+                400
+            }
+            _ => 500,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize() {
+        let contents = include_str!("../../resources/example_error_rate_limited.json");
+        let error: ApiError = serde_json::from_str(contents).unwrap();
+        assert_eq!(error.status, ResponseStatus::Error);
+        assert_eq!(error.code, "rateLimited");
+        assert_eq!(error.http_status(), 429);
+
+        let probe: StatusProbe =
+            serde_json::from_str(include_str!("../../resources/example_sources.json")).unwrap();
+        assert_eq!(probe.status, Some(ResponseStatus::Ok));
+    }
+}
@@ -0,0 +1,179 @@
+use super::article::Article;
+use super::status::ResponseStatus;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_derive::Deserialize;
+use std::fmt;
+use std::io::{BufReader, Read};
+
+/// Everything in a page of articles apart from the articles themselves, as collected by
+/// `for_each_article`
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ArticlesSummary {
+    pub status: Option<ResponseStatus>,
+    pub total_results: usize,
+    /// How many articles were handed to the callback
+    pub count: usize,
+    /// Only present on error bodies
+    pub code: Option<String>,
+    /// Only present on error bodies
+    pub message: Option<String>,
+}
+
+/// Decode a page of articles straight from `reader`, handing each `Article` to `f` as soon as
+/// it has been parsed instead of collecting them into a `Vec` first. Parsing from a reader is
+/// slower per byte than parsing a buffer (see `benches/decode.rs`); what it saves is holding
+/// the whole body and every decoded article in memory at once.
+pub fn for_each_article<R, F>(reader: R, mut f: F) -> Result<ArticlesSummary, serde_json::Error>
+where
+    R: Read,
+    F: FnMut(Article),
+{
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(reader));
+    let summary = PageSeed { f: &mut f }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(summary)
+}
+
+/// Like `for_each_article`, for a body that is already in memory
+pub fn for_each_article_in_slice<F>(
+    body: &[u8],
+    mut f: F,
+) -> Result<ArticlesSummary, serde_json::Error>
+where
+    F: FnMut(Article),
+{
+    let mut deserializer = serde_json::Deserializer::from_slice(body);
+    let summary = PageSeed { f: &mut f }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(summary)
+}
+
+// Identifying keys without allocating a String for each
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "camelCase")]
+enum Field {
+    Status,
+    TotalResults,
+    Code,
+    Message,
+    Articles,
+    #[serde(other)]
+    Other,
+}
+
+struct PageSeed<'f, F> {
+    f: &'f mut F,
+}
+
+impl<'de, F: FnMut(Article)> DeserializeSeed<'de> for PageSeed<'_, F> {
+    type Value = ArticlesSummary;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: FnMut(Article)> Visitor<'de> for PageSeed<'_, F> {
+    type Value = ArticlesSummary;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a page of articles")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut summary = ArticlesSummary::default();
+
+        while let Some(field) = map.next_key::<Field>()? {
+            match field {
+                Field::Status => summary.status = Some(map.next_value()?),
+                Field::TotalResults => summary.total_results = map.next_value()?,
+                Field::Code => summary.code = Some(map.next_value()?),
+                Field::Message => summary.message = Some(map.next_value()?),
+                Field::Articles => {
+                    summary.count = map.next_value_seed(ArticlesSeed { f: &mut *self.f })?;
+                }
+                Field::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        if summary.status.is_none() {
+            return Err(de::Error::missing_field("status"));
+        }
+        Ok(summary)
+    }
+}
+
+struct ArticlesSeed<'f, F> {
+    f: &'f mut F,
+}
+
+impl<'de, F: FnMut(Article)> DeserializeSeed<'de> for ArticlesSeed<'_, F> {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F: FnMut(Article)> Visitor<'de> for ArticlesSeed<'_, F> {
+    type Value = usize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of articles")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut count = 0;
+        while let Some(article) = seq.next_element::<Article>()? {
+            (self.f)(article);
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streams_articles() {
+        let contents = include_str!("../../resources/example_everything.json");
+
+        let mut urls = vec![];
+        let summary =
+            for_each_article(contents.as_bytes(), |article| urls.push(article.url)).unwrap();
+
+        assert_eq!(summary.status, Some(ResponseStatus::Ok));
+        assert_eq!(summary.total_results, 2);
+        assert_eq!(summary.count, 2);
+        assert_eq!(
+            urls[0],
+            "https://www.wired.com/story/anarchy-bitcoin-and-murder-in-mexico/"
+        );
+
+        let mut titles = vec![];
+        let summary =
+            for_each_article_in_slice(contents.as_bytes(), |article| titles.push(article.title))
+                .unwrap();
+        assert_eq!(summary.count, titles.len());
+    }
+
+    #[test]
+    fn error_body() {
+        let contents = include_str!("../../resources/example_error_rate_limited.json");
+        let summary = for_each_article(contents.as_bytes(), |_| panic!("no articles")).unwrap();
+
+        assert_eq!(summary.status, Some(ResponseStatus::Error));
+        assert_eq!(summary.code.as_deref(), Some("rateLimited"));
+        assert_eq!(summary.count, 0);
+    }
+
+    #[test]
+    fn malformed() {
+        assert!(for_each_article(&b"{\"articles\": []}"[..], |_| ()).is_err());
+        assert!(for_each_article(&b"{\"status\": \"ok\", \"articles\": [1]}"[..], |_| ()).is_err());
+    }
+}
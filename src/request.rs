@@ -0,0 +1,455 @@
+use super::api::NewsAPIClient;
+use super::endpoint::{self, Endpoint};
+use super::error::NewsApiError;
+use super::explain::Explanation;
+use super::payload::article::Articles;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use super::payload::{article::Article, stream::ArticlesSummary};
+use super::spec::RequestSpec;
+#[cfg(feature = "async")]
+use super::watch::Watch;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use super::{response::Response, stale::MaybeStale};
+#[cfg(any(feature = "async", feature = "blocking"))]
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+
+/// A request bound to an endpoint, as returned by `everything()`, `top_headlines()` and
+/// `sources()`. Sending it decodes the response into that endpoint's payload: `Articles` for
+/// everything and top headlines, `Sources` for sources.
+///
+/// Only a request knows its endpoint, so only a request can be sent. Its URL is built from the
+/// client's parameters when it is sent, and the client cannot be changed while it is borrowed.
+///
+/// ```compile_fail
+/// # use newsapi::api::NewsAPIClient;
+/// let client = NewsAPIClient::new("key".to_owned());
+/// // there is no endpoint to send to
+/// client.send_sync();
+/// ```
+///
+/// ```compile_fail
+/// # use newsapi::api::NewsAPIClient;
+/// let mut client = NewsAPIClient::new("key".to_owned());
+/// let request = client.everything();
+/// // the request still borrows the client
+/// client.query("rust");
+/// request.send_sync();
+/// ```
+#[derive(Debug)]
+pub struct Request<'a, E> {
+    client: &'a NewsAPIClient,
+    endpoint: PhantomData<E>,
+}
+
+impl<'a, E: Endpoint> Request<'a, E> {
+    pub(crate) fn new(client: &'a NewsAPIClient) -> Request<'a, E> {
+        Request {
+            client,
+            endpoint: PhantomData,
+        }
+    }
+
+    /// Replace the client's parameters with those of a newsapi.org URL for this endpoint, e.g.
+    /// `Request::<Everything>::from_url(&mut client, url)`. See `RequestSpec::from_url` for
+    /// what is accepted.
+    pub fn from_url(
+        client: &'a mut NewsAPIClient,
+        url: &str,
+    ) -> Result<Request<'a, E>, NewsApiError> {
+        Request::from_spec(client, &RequestSpec::from_url(url)?)
+    }
+
+    /// Replace the client's parameters with those of `spec`, which must be for this endpoint.
+    /// Values NewsAPI would not accept, such as a `page_size` over 100, are an error.
+    pub fn from_spec(
+        client: &'a mut NewsAPIClient,
+        spec: &RequestSpec,
+    ) -> Result<Request<'a, E>, NewsApiError> {
+        spec.validate()?;
+        if spec.endpoint_url() != E::URL {
+            return Err(NewsApiError::EndpointMismatch {
+                expected: endpoint::name_of(E::URL).to_owned(),
+                found: endpoint::name_of(spec.endpoint_url()).to_owned(),
+            });
+        }
+        client.request_spec(spec);
+        Ok(Request::new(client))
+    }
+
+    /// The URL that will be sent to NewsAPI
+    pub fn url(&self) -> String {
+        self.client.url_for::<E>()
+    }
+
+    /// Describe what sending this request would do without sending it. Middleware is not run,
+    /// so hooks that rewrite requests are not reflected.
+    pub fn explain(&self) -> Explanation {
+        self.client.explain(&self.url())
+    }
+
+    /// This request as a `RequestSpec`, to store and send again later with `from_spec`. Fails
+    /// with `NewsApiError::InvalidUrlError` if a parameter set as free text cannot be read back.
+    pub fn spec(&self) -> Result<RequestSpec, NewsApiError> {
+        RequestSpec::from_parameters(E::URL, self.client.parameters())
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn client(&self) -> &'a NewsAPIClient {
+        self.client
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn send_async(&self) -> Result<E::Payload, NewsApiError> {
+        self.client.send_async::<E::Payload>(&self.url()).await
+    }
+
+    #[cfg(feature = "blocking")]
+    pub fn send_sync(&self) -> Result<E::Payload, NewsApiError> {
+        self.client.send_sync::<E::Payload>(&self.url())
+    }
+
+    /// See `NewsAPIClient::stale_while_error`
+    #[cfg(feature = "async")]
+    pub async fn send_async_or_stale(&self) -> Result<MaybeStale<E::Payload>, NewsApiError> {
+        self.client
+            .send_async_or_stale::<E::Payload>(&self.url())
+            .await
+    }
+
+    /// See `NewsAPIClient::stale_while_error`
+    #[cfg(feature = "blocking")]
+    pub fn send_sync_or_stale(&self) -> Result<MaybeStale<E::Payload>, NewsApiError> {
+        self.client.send_sync_or_stale::<E::Payload>(&self.url())
+    }
+
+    /// The payload along with the status, headers, timing, fingerprint and raw body of the
+    /// response
+    #[cfg(feature = "async")]
+    pub async fn send_async_with_meta(&self) -> Result<Response<E::Payload>, NewsApiError> {
+        self.client
+            .send_async_with_meta::<E::Payload>(&self.url())
+            .await
+    }
+
+    /// The payload along with the status, headers, timing, fingerprint and raw body of the
+    /// response
+    #[cfg(feature = "blocking")]
+    pub fn send_sync_with_meta(&self) -> Result<Response<E::Payload>, NewsApiError> {
+        self.client.send_sync_with_meta::<E::Payload>(&self.url())
+    }
+
+    /// Decode the response into a type of your choosing instead, e.g. `serde_json::Value` for
+    /// the raw JSON
+    #[cfg(feature = "async")]
+    pub async fn send_async_as<T>(&self) -> Result<T, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        self.client.send_async::<T>(&self.url()).await
+    }
+
+    /// Decode the response into a type of your choosing instead, e.g. `serde_json::Value` for
+    /// the raw JSON
+    #[cfg(feature = "blocking")]
+    pub fn send_sync_as<T>(&self) -> Result<T, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        self.client.send_sync::<T>(&self.url())
+    }
+}
+
+impl<'a, E> Request<'a, E>
+where
+    E: Endpoint<Payload = Articles>,
+{
+    /// Hand each article to `f` as soon as it has been decoded from the response body, which
+    /// is parsed while it is still arriving instead of being buffered first. Returns the rest
+    /// of the page (status, total results and how many articles were seen).
+    #[cfg(feature = "blocking")]
+    pub fn for_each_article_sync<F>(&self, f: F) -> Result<ArticlesSummary, NewsApiError>
+    where
+        F: FnMut(Article),
+    {
+        self.client.for_each_article_sync(&self.url(), f)
+    }
+
+    /// Hand each article to `f` as it is decoded, without building a `Vec<Article>`. Unlike
+    /// `for_each_article_sync` this does not stream: the async transport delivers the whole
+    /// body before decoding starts.
+    #[cfg(feature = "async")]
+    pub async fn for_each_article_async<F>(&self, f: F) -> Result<ArticlesSummary, NewsApiError>
+    where
+        F: FnMut(Article),
+    {
+        self.client.for_each_article_async(&self.url(), f).await
+    }
+
+    /// Send this request over and over, yielding only the articles not seen in earlier
+    /// responses. See `Watch` for the polling interval, quota use and the seen set.
+    #[cfg(feature = "async")]
+    pub fn watch(self) -> Watch<'a, E> {
+        Watch::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants;
+    #[cfg(feature = "async")]
+    use crate::execute::block_on;
+    use crate::spec::EverythingSpec;
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    use crate::{
+        fingerprint::Fingerprint,
+        testing::scripted_client,
+        transport::{HttpResponse, MockTransport, Route},
+    };
+    use chrono::TimeZone;
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    use std::sync::Arc;
+
+    /// A client answering every request with `body`
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    fn client_for(body: &str) -> NewsAPIClient {
+        scripted_client(vec![HttpResponse::new(200, body)]).0
+    }
+
+    #[test]
+    fn url() {
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.page(2).country(constants::Country::Germany);
+        assert_eq!(
+            api.top_headlines().url(),
+            "https://newsapi.org/v2/top-headlines?country=de&page=2"
+        );
+
+        // the URL follows the parameters, however late they are set
+        api.page(3);
+        assert_eq!(
+            api.top_headlines().url(),
+            "https://newsapi.org/v2/top-headlines?country=de&page=3"
+        );
+    }
+
+    #[test]
+    fn spec() {
+        let from = chrono::Utc.with_ymd_and_hms(2024, 5, 1, 8, 30, 0).unwrap();
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.query("rust AND (async OR tokio)")
+            .domains(vec!["lwn.net", "theregister.com"])
+            .from(&from)
+            .language(constants::Language::English)
+            .sort_by(constants::SortMethod::Popularity)
+            .page_size(50);
+        let request = api.everything();
+        let url = request.url();
+        let spec = request.spec().unwrap();
+
+        let stored = serde_json::to_string(&spec).unwrap();
+        let mut replay = NewsAPIClient::new("456".to_owned());
+        replay.page(7);
+        let spec = serde_json::from_str(&stored).unwrap();
+        let request = Request::<endpoint::Everything>::from_spec(&mut replay, &spec).unwrap();
+        assert_eq!(request.url(), url);
+
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.category(constants::Category::Science)
+            .country(constants::Country::Germany);
+        let spec = api.top_headlines().spec().unwrap();
+        assert_eq!(
+            serde_json::to_value(&spec).unwrap(),
+            serde_json::json!({"endpoint": "top-headlines", "country": "de", "category": "science"})
+        );
+        let request = Request::<endpoint::TopHeadlines>::from_spec(&mut replay, &spec).unwrap();
+        assert_eq!(
+            request.url(),
+            "https://newsapi.org/v2/top-headlines?country=de&category=science"
+        );
+        assert!(matches!(
+            Request::<endpoint::Sources>::from_spec(&mut replay, &spec),
+            Err(NewsApiError::EndpointMismatch { .. })
+        ));
+        let oversized = RequestSpec::Everything(EverythingSpec {
+            page_size: Some(150),
+            ..EverythingSpec::default()
+        });
+        assert!(matches!(
+            Request::<endpoint::Everything>::from_spec(&mut replay, &oversized),
+            Err(NewsApiError::InvalidUrlError { .. })
+        ));
+
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.with_sources("%FF".to_owned());
+        assert!(matches!(
+            api.everything().spec(),
+            Err(NewsApiError::InvalidUrlError { .. })
+        ));
+    }
+
+    #[test]
+    fn from_url() {
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.query("+bitcoin -\"crypto winter\"")
+            .with_sources("bbc-news,the-verge".to_owned())
+            .exclude_domains(vec!["example.com"])
+            .to(&chrono::Utc
+                .with_ymd_and_hms(2024, 5, 2, 23, 59, 59)
+                .unwrap())
+            .sort_by(constants::SortMethod::Relevancy)
+            .page(3);
+        let url = api.everything().url();
+        assert!(url.contains("sortBy=relevancy"));
+
+        let mut replay = NewsAPIClient::new("456".to_owned());
+        let request = Request::<endpoint::Everything>::from_url(&mut replay, &url).unwrap();
+        assert_eq!(request.url(), url);
+
+        let mut replay = NewsAPIClient::new("456".to_owned());
+        assert!(matches!(
+            Request::<endpoint::Sources>::from_url(&mut replay, &url),
+            Err(NewsApiError::EndpointMismatch { .. })
+        ));
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn setter_after_everything() {
+        let body = include_str!("../resources/example_everything.json");
+        let mock = Arc::new(MockTransport::new().respond(
+            Route::new(constants::EVERYTHING_URL),
+            HttpResponse::new(200, body),
+        ));
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock.clone()).query("rust");
+        let sent = |api: &NewsAPIClient| {
+            let request = api.everything();
+            each_flavour!(request.send_async(), request.send_sync()).len()
+        };
+
+        let before = sent(&api);
+        api.query("wasm");
+        let after = sent(&api);
+
+        // nothing about the first request sticks to the client, the second one has the new query
+        let urls: Vec<_> = mock.requests().into_iter().map(|r| r.url).collect();
+        assert_eq!(urls.len(), before + after);
+        assert!(urls[..before].iter().all(|url| url.ends_with("?q=rust")));
+        assert!(urls[before..].iter().all(|url| url.ends_with("?q=wasm")));
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn natural_payload() {
+        let body = include_str!("../resources/example_everything.json");
+        let api = client_for(body);
+        let request = api.everything();
+
+        #[cfg(feature = "async")]
+        assert_eq!(block_on(request.send_async()).unwrap().articles.len(), 2);
+        #[cfg(feature = "blocking")]
+        assert_eq!(request.send_sync().unwrap().articles.len(), 2);
+
+        let body = include_str!("../resources/example_sources.json");
+        let api = client_for(body);
+        let request = api.sources();
+
+        #[cfg(feature = "async")]
+        assert_eq!(block_on(request.send_async()).unwrap().sources.len(), 4);
+        #[cfg(feature = "blocking")]
+        assert_eq!(request.send_sync().unwrap().sources.len(), 4);
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn send_with_meta() {
+        let body = include_str!("../resources/example_sources.json");
+        let mock = Arc::new(MockTransport::new().respond(
+            Route::new(constants::SOURCES_URL),
+            HttpResponse::new(200, body).header("X-RateLimit-Remaining", "99"),
+        ));
+        let mut api = NewsAPIClient::new("123".to_owned());
+        api.transport(mock).language(constants::Language::English);
+        let request = api.sources();
+
+        let mut responses = vec![];
+        #[cfg(feature = "async")]
+        responses.push(block_on(request.send_async_with_meta()).unwrap());
+        #[cfg(feature = "blocking")]
+        responses.push(request.send_sync_with_meta().unwrap());
+
+        for response in responses {
+            assert_eq!(response.status, 200);
+            assert_eq!(response.payload.sources.len(), 4);
+            assert_eq!(response.header_value("x-ratelimit-remaining"), Some("99"));
+            assert_eq!(response.url, "https://newsapi.org/v2/sources?language=en");
+            assert_eq!(
+                response.fingerprint,
+                Fingerprint::of_url("https://newsapi.org/v2/sources?language=en")
+            );
+            assert_eq!(response.body, body.as_bytes());
+        }
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn for_each_article() {
+        let body = include_str!("../resources/example_everything.json");
+        let api = client_for(body);
+        let request = api.everything();
+
+        let mut urls = vec![];
+        #[cfg(feature = "async")]
+        {
+            let summary = block_on(request.for_each_article_async(|a| urls.push(a.url))).unwrap();
+            assert_eq!(summary.count, 2);
+        }
+        #[cfg(feature = "blocking")]
+        {
+            let summary = request.for_each_article_sync(|a| urls.push(a.url)).unwrap();
+            assert_eq!(summary.total_results, 2);
+        }
+        assert!(urls.iter().all(|url| url.starts_with("https://")));
+
+        let body = include_str!("../resources/example_error_api_key_invalid.json");
+        let api = client_for(body);
+        let request = api.top_headlines();
+        #[cfg(feature = "async")]
+        assert!(matches!(
+            block_on(request.for_each_article_async(|_| ())),
+            Err(NewsApiError::Unauthorized { .. })
+        ));
+        #[cfg(feature = "blocking")]
+        {
+            let error = request.for_each_article_sync(|_| ()).unwrap_err();
+            assert!(matches!(error, NewsApiError::Unauthorized { .. }));
+            assert_eq!(error.api_error().unwrap().code, "apiKeyInvalid");
+            // streaming reports the error just as a whole body send does
+            assert_eq!(
+                error.to_string(),
+                request.send_sync().unwrap_err().to_string()
+            );
+        }
+    }
+
+    #[cfg(any(feature = "async", feature = "blocking"))]
+    #[test]
+    fn send_as_json() {
+        let body = include_str!("../resources/example_headlines.json");
+        let api = client_for(body);
+        let request = api.top_headlines();
+
+        #[cfg(feature = "async")]
+        {
+            let json = block_on(request.send_async_as::<serde_json::Value>()).unwrap();
+            assert_eq!(json["totalResults"], 2);
+        }
+        #[cfg(feature = "blocking")]
+        {
+            let json = request.send_sync_as::<serde_json::Value>().unwrap();
+            assert_eq!(json["totalResults"], 2);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use super::error::NewsApiError;
+use super::fingerprint::Fingerprint;
+use super::payload::borrowed::ArticlesRef;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+/// A decoded payload together with what was learned while fetching it
+#[derive(Debug, Clone)]
+pub struct Response<T> {
+    pub payload: T,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Time spent waiting on the transport
+    pub elapsed: Duration,
+    /// The URL the response came from, after any redirects the transport followed
+    pub url: String,
+    pub fingerprint: Fingerprint,
+    /// The undecoded response body
+    pub body: Vec<u8>,
+}
+
+impl<T> Response<T> {
+    /// The value of the first header called `name`, compared case insensitively
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn into_payload(self) -> T {
+        self.payload
+    }
+
+    /// Decode the raw body again as articles borrowing from it, see `ArticlesRef`
+    pub fn articles_ref(&self) -> Result<ArticlesRef<'_>, NewsApiError> {
+        Ok(ArticlesRef::from_slice(&self.body)?)
+    }
+}
+
+impl Response<()> {
+    pub(crate) fn decode<T>(self) -> Result<Response<T>, NewsApiError>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(Response {
+            payload: serde_json::from_slice::<T>(&self.body)?,
+            status: self.status,
+            headers: self.headers,
+            elapsed: self.elapsed,
+            url: self.url,
+            fingerprint: self.fingerprint,
+            body: self.body,
+        })
+    }
+}
@@ -0,0 +1,257 @@
+use super::error::NewsApiError;
+use super::fingerprint::Fingerprint;
+use super::response::Response;
+use super::timer::sleep_until;
+use std::collections::HashMap;
+use std::future::{poll_fn, Future};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+use std::time::Instant;
+
+/// Lets concurrent identical `send_async` requests share one call to NewsAPI. Requests are
+/// identical when their URLs have the same `Fingerprint`, whichever key they would be sent
+/// with. The first one calls NewsAPI and the others wait for its response (or error) instead
+/// of sending their own. Share one between the clients of a service with
+/// `NewsAPIClient::single_flight`.
+#[derive(Debug, Default)]
+pub struct SingleFlight {
+    calls: Mutex<HashMap<Fingerprint, Arc<Call>>>,
+    saved: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Call {
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+enum State {
+    /// Callers waiting for the result
+    Running(Vec<Waker>),
+    Done(Result<Response<()>, NewsApiError>),
+    /// The caller making the call gave up on it, e.g. it was cancelled
+    Abandoned,
+}
+
+impl SingleFlight {
+    pub fn new() -> SingleFlight {
+        SingleFlight::default()
+    }
+
+    /// Requests answered with another request's response instead of calling NewsAPI
+    pub fn saved(&self) -> u64 {
+        self.saved.load(Ordering::SeqCst)
+    }
+
+    /// Run `call` unless an identical one is in flight, in which case share its result. A
+    /// caller sharing another's call waits no later than its own `deadline`, then fails with
+    /// `NewsApiError::Timeout`. The flag is true when the result was shared.
+    pub(crate) async fn run<F>(
+        &self,
+        fingerprint: Fingerprint,
+        deadline: Option<Instant>,
+        call: F,
+    ) -> (Result<Response<()>, NewsApiError>, bool)
+    where
+        F: Future<Output = Result<Response<()>, NewsApiError>>,
+    {
+        loop {
+            let (shared, leading) = {
+                let mut calls = self.calls.lock().unwrap();
+                match calls.get(&fingerprint) {
+                    Some(shared) => (shared.clone(), false),
+                    None => {
+                        let shared = Arc::new(Call {
+                            state: Mutex::new(State::Running(vec![])),
+                        });
+                        calls.insert(fingerprint, shared.clone());
+                        (shared, true)
+                    }
+                }
+            };
+
+            if leading {
+                let mut leader = Leader {
+                    flight: self,
+                    fingerprint,
+                    call: shared,
+                    finished: false,
+                };
+                let result = call.await;
+                leader.finish(&result);
+                return (result, false);
+            }
+
+            match shared.wait(deadline).await {
+                Waited::Done(result) => {
+                    self.saved.fetch_add(1, Ordering::SeqCst);
+                    return (result, true);
+                }
+                Waited::TimedOut => return (Err(NewsApiError::Timeout), false),
+                // try again, probably as the new leader
+                Waited::Abandoned => {}
+            }
+        }
+    }
+}
+
+/// How waiting for the leader of a call ended
+enum Waited {
+    Done(Result<Response<()>, NewsApiError>),
+    Abandoned,
+    TimedOut,
+}
+
+impl Call {
+    /// The leader's result, unless it was abandoned or `deadline` passed first
+    async fn wait(&self, deadline: Option<Instant>) -> Waited {
+        let mut timer = deadline.map(|deadline| Box::pin(sleep_until(deadline)));
+        poll_fn(|cx| {
+            let mut state = self.state.lock().unwrap();
+            match &mut *state {
+                State::Running(wakers) => {
+                    if let Some(timer) = &mut timer {
+                        if timer.as_mut().poll(cx).is_ready() {
+                            return Poll::Ready(Waited::TimedOut);
+                        }
+                    }
+                    if !wakers.iter().any(|known| known.will_wake(cx.waker())) {
+                        wakers.push(cx.waker().clone());
+                    }
+                    Poll::Pending
+                }
+                State::Done(result) => Poll::Ready(Waited::Done(result.clone())),
+                State::Abandoned => Poll::Ready(Waited::Abandoned),
+            }
+        })
+        .await
+    }
+}
+
+/// Publishes the leader's result, or marks the call abandoned if the leader is dropped first
+struct Leader<'a> {
+    flight: &'a SingleFlight,
+    fingerprint: Fingerprint,
+    call: Arc<Call>,
+    finished: bool,
+}
+
+impl Leader<'_> {
+    fn finish(&mut self, result: &Result<Response<()>, NewsApiError>) {
+        self.settle(State::Done(result.clone()));
+        self.finished = true;
+    }
+
+    fn settle(&self, outcome: State) {
+        {
+            let mut calls = self.flight.calls.lock().unwrap();
+            if calls
+                .get(&self.fingerprint)
+                .is_some_and(|current| Arc::ptr_eq(current, &self.call))
+            {
+                calls.remove(&self.fingerprint);
+            }
+        }
+        let previous = std::mem::replace(&mut *self.call.state.lock().unwrap(), outcome);
+        if let State::Running(wakers) = previous {
+            for waker in wakers {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.settle(State::Abandoned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execute::block_on;
+    use std::time::Duration;
+
+    fn response(url: &str) -> Response<()> {
+        Response {
+            payload: (),
+            status: 200,
+            headers: vec![],
+            elapsed: Duration::ZERO,
+            url: url.to_owned(),
+            fingerprint: Fingerprint::of_url(url),
+            body: b"{}".to_vec(),
+        }
+    }
+
+    #[test]
+    fn abandoned_leader() {
+        let flight = SingleFlight::new();
+        let fingerprint = Fingerprint::of_url("https://newsapi.org/v2/sources");
+
+        // a leader that never finishes, dropped after its first poll
+        let mut leader = Box::pin(flight.run(fingerprint, None, std::future::pending()));
+        let waker = Waker::noop();
+        let mut cx = std::task::Context::from_waker(waker);
+        assert!(leader.as_mut().poll(&mut cx).is_pending());
+        drop(leader);
+
+        let (result, shared) = block_on(flight.run(fingerprint, None, async {
+            Ok(response("https://newsapi.org/v2/sources"))
+        }));
+        assert_eq!(result.unwrap().status, 200);
+        assert!(!shared);
+        assert_eq!(flight.saved(), 0);
+        assert!(flight.calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn shared_error_keeps_its_kind() {
+        let flight = SingleFlight::new();
+        let fingerprint = Fingerprint::of_url("https://newsapi.org/v2/sources");
+        let mut cx = std::task::Context::from_waker(Waker::noop());
+
+        let mut polled = false;
+        let failing = poll_fn(|_| {
+            if !polled {
+                polled = true;
+                return Poll::Pending;
+            }
+            Poll::Ready(Err(serde_json::from_str::<u32>("{").unwrap_err().into()))
+        });
+        let mut leader = Box::pin(flight.run(fingerprint, None, failing));
+        let mut follower = Box::pin(flight.run(fingerprint, None, std::future::pending()));
+        assert!(leader.as_mut().poll(&mut cx).is_pending());
+        assert!(follower.as_mut().poll(&mut cx).is_pending());
+        assert!(leader.as_mut().poll(&mut cx).is_ready());
+
+        match follower.as_mut().poll(&mut cx) {
+            Poll::Ready((Err(NewsApiError::SerdeError { .. }), true)) => {}
+            other => panic!("expected a shared SerdeError, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn follower_deadline() {
+        let flight = SingleFlight::new();
+        let fingerprint = Fingerprint::of_url("https://newsapi.org/v2/sources");
+
+        let mut leader = Box::pin(flight.run(fingerprint, None, std::future::pending()));
+        let mut cx = std::task::Context::from_waker(Waker::noop());
+        assert!(leader.as_mut().poll(&mut cx).is_pending());
+
+        // the follower gives up at its own deadline although the leader is still running
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let (result, shared) = flight
+            .run(fingerprint, Some(deadline), std::future::pending())
+            .await;
+        assert!(matches!(result, Err(NewsApiError::Timeout)));
+        assert!(!shared);
+        assert!(Instant::now() >= deadline);
+        assert_eq!(flight.saved(), 0);
+    }
+}
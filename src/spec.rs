@@ -0,0 +1,451 @@
+//! Requests as plain data, for saved searches in config files and for replaying a request
+//! later.
+//!
+//! The format is an object with an `endpoint` tag (`everything`, `top-headlines` or
+//! `sources`) and that endpoint's parameters in snake case. Parameters left out are not sent,
+//! unknown ones are rejected. Countries, languages, categories and sort orders use the codes
+//! NewsAPI uses, dates are RFC 3339 and are sent to the second. In TOML:
+//!
+//! ```toml
+//! endpoint = "everything"
+//! query = "rust AND (async OR tokio)"
+//! domains = ["theregister.com", "lwn.net"]
+//! from = "2024-05-01T00:00:00Z"
+//! language = "en"
+//! page_size = 50
+//! ```
+
+use super::constants::{Category, Country, Language, SortMethod};
+use super::endpoint::{self, Endpoint};
+use super::error::NewsApiError;
+use super::key::API_KEY_PARAMETER;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use percent_encoding::percent_decode_str;
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// A request to one of the NewsAPI endpoints, see the module documentation for the format
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "endpoint", rename_all = "kebab-case")]
+pub enum RequestSpec {
+    Everything(EverythingSpec),
+    TopHeadlines(TopHeadlinesSpec),
+    Sources(SourcesSpec),
+}
+
+/// https://newsapi.org/docs/endpoints/everything
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EverythingSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude_domains: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<SortMethod>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_page_size"
+    )]
+    pub page_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+}
+
+/// https://newsapi.org/docs/endpoints/top-headlines
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TopHeadlinesSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<Country>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<Category>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_page_size"
+    )]
+    pub page_size: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+}
+
+/// https://newsapi.org/docs/endpoints/sources
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourcesSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<Category>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<Language>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<Country>,
+}
+
+impl RequestSpec {
+    /// The URL of the endpoint this spec is for
+    pub fn endpoint_url(&self) -> &'static str {
+        match self {
+            RequestSpec::Everything(_) => endpoint::Everything::URL,
+            RequestSpec::TopHeadlines(_) => endpoint::TopHeadlines::URL,
+            RequestSpec::Sources(_) => endpoint::Sources::URL,
+        }
+    }
+
+    /// Check the values the types allow but NewsAPI would not accept, e.g. a `page_size` of 0.
+    /// Deserializing already rejects them; specs built in code are checked before they are sent.
+    pub(crate) fn validate(&self) -> Result<(), NewsApiError> {
+        let page_size = match self {
+            RequestSpec::Everything(spec) => spec.page_size,
+            RequestSpec::TopHeadlines(spec) => spec.page_size,
+            RequestSpec::Sources(_) => None,
+        };
+        check_page_size("page_size", page_size).map_err(invalid)?;
+        Ok(())
+    }
+
+    /// Read a request out of a newsapi.org URL, e.g. one tried out in a browser. Names and
+    /// values may be percent-encoded, with `+` for a space, and an `apiKey` parameter is
+    /// ignored; parameters the endpoint does not take, repeated parameters and values NewsAPI
+    /// would not accept are errors. A `from` or `to` without a time means midnight UTC at the
+    /// start of that day, so `to=2024-05-01` leaves out the articles of May 1st.
+    pub fn from_url(url: &str) -> Result<RequestSpec, NewsApiError> {
+        let url = url.split_once('#').map(|(url, _)| url).unwrap_or(url);
+        let (base, query) = url.split_once('?').unwrap_or((url, ""));
+        let base = base.trim_end_matches('/');
+        let allowed = endpoint::parameters_of(base)
+            .ok_or_else(|| invalid(format!("{base} is not a NewsAPI endpoint")))?;
+
+        let mut parameters = HashMap::new();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let name = percent_decode_str(&name.replace('+', " "))
+                .decode_utf8()
+                .map_err(|_| invalid(format!("{name} is not valid UTF-8 once decoded")))?
+                .into_owned();
+            let name = name.as_str();
+            // values stay encoded until they are read, like the client's own parameters
+            let value = value.replace('+', "%20");
+            if name == API_KEY_PARAMETER {
+                continue;
+            }
+            if !allowed.contains(&name) {
+                return Err(invalid(format!(
+                    "{} does not take the parameter {}",
+                    endpoint::name_of(base),
+                    name
+                )));
+            }
+            if parameters.insert(name.to_owned(), value).is_some() {
+                return Err(invalid(format!("{name} is given more than once")));
+            }
+        }
+        RequestSpec::parse(base, &parameters)
+    }
+
+    /// Read the spec back out of the client's parameters for the endpoint at `url`. Setters
+    /// that take free text, such as `with_sources`, can leave a value the spec cannot hold.
+    pub(crate) fn from_parameters(
+        url: &str,
+        parameters: &HashMap<String, String>,
+    ) -> Result<RequestSpec, NewsApiError> {
+        RequestSpec::parse(url, parameters)
+    }
+
+    fn parse(url: &str, parameters: &HashMap<String, String>) -> Result<RequestSpec, NewsApiError> {
+        let p = Parameters(parameters);
+        let spec = if url == endpoint::Everything::URL {
+            RequestSpec::Everything(EverythingSpec {
+                query: p.text("q")?,
+                sources: p.list("sources")?,
+                domains: p.list("domains")?,
+                exclude_domains: p.list("excludeDomains")?,
+                from: p.date_time("from")?,
+                to: p.date_time("to")?,
+                language: p.code("language")?,
+                sort_by: p.code("sortBy")?,
+                page_size: p.page_size()?,
+                page: p.number("page")?,
+            })
+        } else if url == endpoint::TopHeadlines::URL {
+            RequestSpec::TopHeadlines(TopHeadlinesSpec {
+                query: p.text("q")?,
+                country: p.code("country")?,
+                category: p.code("category")?,
+                sources: p.list("sources")?,
+                page_size: p.page_size()?,
+                page: p.number("page")?,
+            })
+        } else {
+            RequestSpec::Sources(SourcesSpec {
+                category: p.code("category")?,
+                language: p.code("language")?,
+                country: p.code("country")?,
+            })
+        };
+        Ok(spec)
+    }
+}
+
+fn invalid(message: String) -> NewsApiError {
+    NewsApiError::InvalidUrlError { message }
+}
+
+/// `size` if NewsAPI accepts it as the page size given as `name`
+fn check_page_size(name: &str, size: Option<u32>) -> Result<Option<u32>, String> {
+    match size {
+        Some(size) if !(1..=100).contains(&size) => {
+            Err(format!("{name}={size} is not between 1 and 100"))
+        }
+        size => Ok(size),
+    }
+}
+
+fn deserialize_page_size<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u32>, D::Error> {
+    check_page_size("page_size", Option::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+/// Parameters as they appear in a URL, percent-encoded or not
+struct Parameters<'a>(&'a HashMap<String, String>);
+
+impl Parameters<'_> {
+    fn text(&self, name: &str) -> Result<Option<String>, NewsApiError> {
+        let value = match self.0.get(name) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        percent_decode_str(value)
+            .decode_utf8()
+            .map(|decoded| Some(decoded.into_owned()))
+            .map_err(|_| invalid(format!("{name} is not valid UTF-8 once decoded")))
+    }
+
+    fn list(&self, name: &str) -> Result<Vec<String>, NewsApiError> {
+        let value = self.text(name)?;
+        Ok(value
+            .map(|value| value.split(',').map(str::to_owned).collect())
+            .unwrap_or_default())
+    }
+
+    fn date_time(&self, name: &str) -> Result<Option<DateTime<Utc>>, NewsApiError> {
+        let value = match self.text(name)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let parsed = NaiveDateTime::parse_from_str(&value, "%Y-%m-%dT%H:%M:%S")
+            .map(|naive| naive.and_utc())
+            .or_else(|_| DateTime::parse_from_rfc3339(&value).map(|dt| dt.with_timezone(&Utc)))
+            .or_else(|_| {
+                NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+                    .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+            });
+        parsed
+            .map(Some)
+            .map_err(|_| invalid(format!("{name}={value} is not a date or date and time")))
+    }
+
+    fn code<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, NewsApiError> {
+        let value = match self.text(name)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        serde_json::from_value(Value::String(value.clone()))
+            .map(Some)
+            .map_err(|_| invalid(format!("{name}={value} is not a value NewsAPI accepts")))
+    }
+
+    fn number(&self, name: &str) -> Result<Option<u32>, NewsApiError> {
+        let value = match self.text(name)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        value
+            .parse()
+            .map(Some)
+            .map_err(|_| invalid(format!("{name}={value} is not a positive number")))
+    }
+
+    fn page_size(&self) -> Result<Option<u32>, NewsApiError> {
+        check_page_size("pageSize", self.number("pageSize")?).map_err(invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn format() {
+        let spec: RequestSpec = serde_json::from_str(
+            r#"{
+                "endpoint": "everything",
+                "query": "rust AND async",
+                "domains": ["lwn.net"],
+                "from": "2024-05-01T00:00:00Z",
+                "language": "en",
+                "sort_by": "publishedAt",
+                "page_size": 50
+            }"#,
+        )
+        .unwrap();
+
+        let everything = match &spec {
+            RequestSpec::Everything(everything) => everything,
+            _ => panic!("not an everything spec"),
+        };
+        assert_eq!(everything.language, Some(Language::English));
+        assert_eq!(everything.sort_by, Some(SortMethod::PublishedAt));
+        assert_eq!(everything.domains, ["lwn.net"]);
+        assert_eq!(spec.endpoint_url(), endpoint::Everything::URL);
+
+        let round_tripped: RequestSpec =
+            serde_json::from_value(serde_json::to_value(&spec).unwrap()).unwrap();
+        assert_eq!(round_tripped, spec);
+
+        let sources = RequestSpec::Sources(SourcesSpec {
+            country: Some(Country::Germany),
+            ..SourcesSpec::default()
+        });
+        assert_eq!(
+            serde_json::to_value(&sources).unwrap(),
+            serde_json::json!({"endpoint": "sources", "country": "de"})
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_parameters() {
+        let typo = r#"{"endpoint": "top-headlines", "contry": "de"}"#;
+        assert!(serde_json::from_str::<RequestSpec>(typo).is_err());
+
+        let wrong_endpoint = r#"{"endpoint": "sources", "query": "rust"}"#;
+        assert!(serde_json::from_str::<RequestSpec>(wrong_endpoint).is_err());
+    }
+
+    #[test]
+    fn rejects_page_size() {
+        for size in [0, 150] {
+            let json = format!(r#"{{"endpoint": "everything", "page_size": {size}}}"#);
+            let error = serde_json::from_str::<RequestSpec>(&json).unwrap_err();
+            assert!(error.to_string().contains("is not between 1 and 100"));
+
+            let spec = RequestSpec::TopHeadlines(TopHeadlinesSpec {
+                page_size: Some(size),
+                ..TopHeadlinesSpec::default()
+            });
+            match spec.validate() {
+                Err(NewsApiError::InvalidUrlError { message }) => {
+                    assert_eq!(
+                        message,
+                        format!("page_size={size} is not between 1 and 100")
+                    )
+                }
+                other => panic!("page_size {} gave {:?}", size, other),
+            }
+        }
+        let json = r#"{"endpoint": "everything", "page_size": 100}"#;
+        let spec = serde_json::from_str::<RequestSpec>(json).unwrap();
+        assert!(spec.validate().is_ok());
+    }
+
+    #[test]
+    fn from_url() {
+        let spec = RequestSpec::from_url(
+            "https://newsapi.org/v2/everything?q=%22open%20source%22%20AND%20rust\
+             &domains=lwn.net%2Ctheregister.com&from=2024-05-01&sortBy=popularity\
+             &language=de&apiKey=secret",
+        )
+        .unwrap();
+        assert_eq!(
+            spec,
+            RequestSpec::Everything(EverythingSpec {
+                query: Some("\"open source\" AND rust".to_owned()),
+                domains: vec!["lwn.net".to_owned(), "theregister.com".to_owned()],
+                from: Some(Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap()),
+                language: Some(Language::German),
+                sort_by: Some(SortMethod::Popularity),
+                ..EverythingSpec::default()
+            })
+        );
+
+        let spec = RequestSpec::from_url(
+            "https://newsapi.org/v2/top-headlines/?country=us&category=health",
+        )
+        .unwrap();
+        assert_eq!(
+            spec,
+            RequestSpec::TopHeadlines(TopHeadlinesSpec {
+                country: Some(Country::UnitedStatesofAmerica),
+                category: Some(Category::Health),
+                ..TopHeadlinesSpec::default()
+            })
+        );
+
+        // as a browser sends a form: `+` for a space and encoded names
+        let spec = RequestSpec::from_url(
+            "https://newsapi.org/v2/everything?q=rust+AND+c%2B%2B&sort%42y=popularity",
+        )
+        .unwrap();
+        assert_eq!(
+            spec,
+            RequestSpec::Everything(EverythingSpec {
+                query: Some("rust AND c++".to_owned()),
+                sort_by: Some(SortMethod::Popularity),
+                ..EverythingSpec::default()
+            })
+        );
+    }
+
+    #[test]
+    fn from_url_rejects() {
+        for (url, message) in [
+            (
+                "https://newsapi.org/v2/sources?language=en&q=rust",
+                "sources does not take the parameter q",
+            ),
+            (
+                "https://newsapi.org/v2/everything?q=a&q=b",
+                "q is given more than once",
+            ),
+            (
+                "https://newsapi.org/v2/sources?country=xx",
+                "country=xx is not a value NewsAPI accepts",
+            ),
+            (
+                "https://newsapi.org/v2/everything?pageSize=500",
+                "pageSize=500 is not between 1 and 100",
+            ),
+            (
+                "https://newsapi.org/v2/everything?from=yesterday",
+                "from=yesterday is not a date or date and time",
+            ),
+            (
+                "https://example.com/v2/everything",
+                "https://example.com/v2/everything is not a NewsAPI endpoint",
+            ),
+        ] {
+            match RequestSpec::from_url(url) {
+                Err(NewsApiError::InvalidUrlError { message: m }) => assert_eq!(m, message),
+                other => panic!("{} gave {:?}", url, other),
+            }
+        }
+    }
+}
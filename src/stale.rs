@@ -0,0 +1,176 @@
+use super::error::NewsApiError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Requests a `StaleCache` keeps a response for unless told otherwise
+const DEFAULT_CAPACITY: usize = 1_000;
+
+/// Keeps the last successful response body for each request so it can be served when NewsAPI
+/// is unavailable (quota exhausted, rate limited or failing server side). Responses older than
+/// the staleness limit are dropped, and past `capacity` requests the oldest response goes.
+#[derive(Debug)]
+pub struct StaleCache {
+    max_staleness: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, StaleEntry>>,
+}
+
+#[derive(Debug)]
+struct StaleEntry {
+    body: Vec<u8>,
+    stored_at: Instant,
+}
+
+impl StaleCache {
+    /// Responses older than `max_staleness` are never served.
+    pub fn new(max_staleness: Duration) -> StaleCache {
+        StaleCache {
+            max_staleness,
+            capacity: DEFAULT_CAPACITY,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Keep responses for at most `capacity` requests, 1,000 by default
+    pub fn capacity(mut self, capacity: usize) -> StaleCache {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn max_staleness(&self) -> Duration {
+        self.max_staleness
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record the body of a successful response, dropping responses that are too old to serve
+    /// and, when the cache is full, the oldest one
+    pub fn store(&self, key: &str, body: &[u8]) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.stored_at.elapsed() <= self.max_staleness);
+        if entries.len() >= self.capacity && !entries.contains_key(key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key.to_owned(),
+            StaleEntry {
+                body: body.to_vec(),
+                stored_at: Instant::now(),
+            },
+        );
+    }
+
+    /// The last good body for `key` and its age, if it is within the staleness limit
+    pub fn lookup(&self, key: &str) -> Option<(Vec<u8>, Duration)> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(key)?;
+        let age = entry.stored_at.elapsed();
+
+        if age <= self.max_staleness {
+            Some((entry.body.clone(), age))
+        } else {
+            None
+        }
+    }
+}
+
+/// Why a value was served from the stale cache
+#[derive(Debug)]
+pub struct Staleness {
+    /// Time since the value was fetched from NewsAPI
+    pub age: Duration,
+    /// The error returned by the request that the stale value stands in for
+    pub error: NewsApiError,
+}
+
+/// A decoded response that may have come from the stale cache instead of NewsAPI
+#[derive(Debug)]
+pub struct MaybeStale<T> {
+    pub value: T,
+    pub staleness: Option<Staleness>,
+}
+
+impl<T> MaybeStale<T> {
+    pub fn fresh(value: T) -> MaybeStale<T> {
+        MaybeStale {
+            value,
+            staleness: None,
+        }
+    }
+
+    pub fn stale(value: T, age: Duration, error: NewsApiError) -> MaybeStale<T> {
+        MaybeStale {
+            value,
+            staleness: Some(Staleness { age, error }),
+        }
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.staleness.is_some()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let cache = StaleCache::new(Duration::from_secs(60));
+        assert!(cache.lookup("https://newsapi.org/v2/sources").is_none());
+
+        cache.store("https://newsapi.org/v2/sources", b"{}");
+        let (body, age) = cache.lookup("https://newsapi.org/v2/sources").unwrap();
+        assert_eq!(body, b"{}");
+        assert!(age <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn lookup_expired() {
+        let cache = StaleCache::new(Duration::from_secs(0));
+        cache.store("https://newsapi.org/v2/sources", b"{}");
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(cache.lookup("https://newsapi.org/v2/sources").is_none());
+    }
+
+    #[test]
+    fn bounded() {
+        let cache = StaleCache::new(Duration::from_secs(60)).capacity(2);
+        cache.store("a", b"1");
+        cache.store("b", b"2");
+        cache.store("a", b"3");
+        assert_eq!(cache.len(), 2);
+
+        // "b" is the oldest now that "a" was stored again
+        cache.store("c", b"4");
+        assert_eq!(cache.len(), 2);
+        assert!(cache.lookup("b").is_none());
+        assert_eq!(cache.lookup("a").unwrap().0, b"3");
+    }
+
+    #[test]
+    fn drops_expired() {
+        let cache = StaleCache::new(Duration::from_millis(1));
+        cache.store("a", b"1");
+        std::thread::sleep(Duration::from_millis(5));
+        cache.store("b", b"2");
+        assert_eq!(cache.len(), 1);
+    }
+}
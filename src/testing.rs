@@ -0,0 +1,62 @@
+//! Helpers shared by the tests of several modules
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+use crate::{api::NewsAPIClient, middleware::Flow, transport::HttpResponse};
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::collections::VecDeque;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::sync::{Arc, Mutex};
+
+/// Evaluate a send once for every enabled flavour, so a test covers the sync and async paths
+/// alike: `each_flavour!(request.send_async(), request.send_sync())`. The async future is
+/// driven by `block_on`, so it must not wait on a real transport.
+#[cfg(any(feature = "async", feature = "blocking"))]
+macro_rules! each_flavour {
+    ($send_async:expr, $send_sync:expr $(,)?) => {
+        vec![
+            #[cfg(feature = "async")]
+            $crate::execute::block_on($send_async),
+            #[cfg(feature = "blocking")]
+            $send_sync,
+        ]
+    };
+}
+
+/// An articles response listing `(url, publishedAt)` in the order given, each titled with its
+/// URL
+pub(crate) fn articles_page<P: AsRef<str>>(articles: &[(&str, P)], total: usize) -> String {
+    let articles: Vec<_> = articles
+        .iter()
+        .map(|(url, published_at)| {
+            serde_json::json!({
+                "source": {"id": null, "name": "Example"},
+                "title": url,
+                "url": url,
+                "publishedAt": published_at.as_ref(),
+            })
+        })
+        .collect();
+    serde_json::json!({"status": "ok", "totalResults": total, "articles": articles}).to_string()
+}
+
+/// A client answering its requests with `responses` in turn, repeating the last one once they
+/// run out, and the URLs it was sent. Nothing reaches a transport.
+#[cfg(any(feature = "async", feature = "blocking"))]
+pub(crate) fn scripted_client(
+    responses: Vec<HttpResponse>,
+) -> (NewsAPIClient, Arc<Mutex<Vec<String>>>) {
+    let sent = Arc::new(Mutex::new(vec![]));
+    let responses = Mutex::new(VecDeque::from(responses));
+    let mut api = NewsAPIClient::new("123".to_owned());
+    let log = sent.clone();
+    api.before_request(move |request| {
+        log.lock().unwrap().push(request.url.clone());
+        let mut responses = responses.lock().unwrap();
+        let response = match responses.len() {
+            1 => responses[0].clone(),
+            _ => responses.pop_front().expect("a response to send"),
+        };
+        Ok(Flow::Respond(response))
+    });
+    (api, sent)
+}
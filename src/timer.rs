@@ -0,0 +1,140 @@
+//! One background thread that wakes tasks when their deadline passes, shared by every wait so
+//! no async runtime is needed and no thread is started per wait.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::future::poll_fn;
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::task::{Poll, Waker};
+use std::time::Instant;
+
+static TIMER: OnceLock<Timer> = OnceLock::new();
+
+#[derive(Default)]
+struct Timer {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    /// Deadlines, earliest first. Entries of waits that were dropped stay until they are due.
+    deadlines: BinaryHeap<Reverse<(Instant, u64)>>,
+    wakers: HashMap<u64, Waker>,
+}
+
+/// A wait registered with the timer, deregistered when it finishes or is dropped
+struct Registration {
+    id: Option<u64>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            timer().state.lock().unwrap().wakers.remove(&id);
+        }
+    }
+}
+
+/// Wait until `deadline`
+pub(crate) async fn sleep_until(deadline: Instant) {
+    let mut registration = Registration { id: None };
+    poll_fn(|cx| {
+        if Instant::now() >= deadline {
+            return Poll::Ready(());
+        }
+        let timer = timer();
+        let mut state = timer.state.lock().unwrap();
+        match registration.id {
+            Some(id) => {
+                state.wakers.insert(id, cx.waker().clone());
+            }
+            None => {
+                state.next_id += 1;
+                let id = state.next_id;
+                state.deadlines.push(Reverse((deadline, id)));
+                state.wakers.insert(id, cx.waker().clone());
+                registration.id = Some(id);
+                timer.changed.notify_one();
+            }
+        }
+        Poll::Pending
+    })
+    .await
+}
+
+fn timer() -> &'static Timer {
+    TIMER.get_or_init(|| {
+        std::thread::Builder::new()
+            .name("newsapi-timer".to_owned())
+            .spawn(run)
+            .expect("the timer thread starts");
+        Timer::default()
+    })
+}
+
+fn run() {
+    let timer = timer();
+    let mut state = timer.state.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        while let Some(Reverse((deadline, id))) = state.deadlines.peek().copied() {
+            if deadline > now {
+                break;
+            }
+            state.deadlines.pop();
+            if let Some(waker) = state.wakers.remove(&id) {
+                waker.wake();
+            }
+        }
+
+        state = match state.deadlines.peek() {
+            Some(Reverse((deadline, _))) => {
+                let wait = deadline.saturating_duration_since(now);
+                timer.changed.wait_timeout(state, wait).unwrap().0
+            }
+            None => timer.changed.wait(state).unwrap(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::task::Context;
+    use std::time::Duration;
+
+    /// How many waits for exactly `deadline` are registered
+    fn waiting(deadline: Instant) -> usize {
+        let state = timer().state.lock().unwrap();
+        state
+            .deadlines
+            .iter()
+            .filter(|Reverse((at, id))| *at == deadline && state.wakers.contains_key(id))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn wakes_at_deadline() {
+        let started = Instant::now();
+        let waits = (1..=3).map(|n| sleep_until(started + Duration::from_millis(10 * n)));
+        for wait in waits {
+            wait.await;
+        }
+        assert!(started.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn dropped_wait_deregisters() {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let mut wait = Box::pin(sleep_until(deadline));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(wait.as_mut().poll(&mut cx).is_pending());
+        assert!(wait.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(waiting(deadline), 1);
+        drop(wait);
+        assert_eq!(waiting(deadline), 0);
+    }
+}
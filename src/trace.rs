@@ -0,0 +1,331 @@
+//! `tracing` instrumentation, compiled to nothing unless the `tracing` feature is enabled.
+//!
+//! Every request runs inside a `newsapi.request` span carrying the endpoint, the query
+//! parameters (credentials redacted), the page, the attempt number and, once known, the HTTP
+//! status, latency and error. Stale cache, quota, key failover and circuit
+//! breaker decisions are reported as events.
+
+use super::circuit::CircuitState;
+use super::error::NewsApiError;
+#[cfg(any(feature = "tracing", test))]
+use super::{endpoint, key};
+use std::future::Future;
+use std::time::Duration;
+
+/// The query string of `url` with the API key redacted
+#[cfg(any(feature = "tracing", test))]
+pub(crate) fn redacted_parameters(url: &str) -> String {
+    let url = key::redact_url(url);
+    url.split_once('?')
+        .map(|(_, query)| query.to_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(any(feature = "tracing", test))]
+fn page(url: &str) -> u32 {
+    url.split_once('?')
+        .map(|(_, query)| query)
+        .unwrap_or("")
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("page="))
+        .and_then(|page| page.parse().ok())
+        .unwrap_or(1)
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) struct RequestSpan(tracing::Span);
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct RequestSpan;
+
+impl RequestSpan {
+    #[cfg(feature = "tracing")]
+    pub(crate) fn new(url: &str, attempt: u32) -> RequestSpan {
+        RequestSpan(tracing::info_span!(
+            "newsapi.request",
+            endpoint = endpoint::name_of(url),
+            parameters = %redacted_parameters(url),
+            page = page(url),
+            attempt,
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+            error = tracing::field::Empty,
+        ))
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[allow(unused_variables)]
+    pub(crate) fn new(url: &str, attempt: u32) -> RequestSpan {
+        RequestSpan
+    }
+
+    /// Run `future` inside the span
+    #[cfg(feature = "tracing")]
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        tracing::Instrument::instrument(future, self.0.clone())
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        future
+    }
+
+    #[allow(unused_variables)]
+    pub(crate) fn record_response(&self, status: u16, latency: Duration) {
+        #[cfg(feature = "tracing")]
+        {
+            self.0.record("status", status);
+            self.0.record("latency_ms", latency.as_millis() as u64);
+        }
+    }
+
+    /// Record the error the request failed with, and report quota refusals inside the span
+    #[allow(unused_variables)]
+    pub(crate) fn record_error(&self, error: &NewsApiError) {
+        #[cfg(feature = "tracing")]
+        {
+            self.0.record("error", tracing::field::display(error));
+            let _entered = self.0.enter();
+            quota(error);
+        }
+    }
+}
+
+/// A failed request was answered from the stale cache, or could not be
+#[allow(unused_variables)]
+pub(crate) fn stale_cache(url: &str, age: Option<Duration>, error: &NewsApiError) {
+    #[cfg(feature = "tracing")]
+    match age {
+        Some(age) => tracing::info!(
+            endpoint = endpoint::name_of(url),
+            parameters = %redacted_parameters(url),
+            age_ms = age.as_millis() as u64,
+            %error,
+            "newsapi.stale_cache.hit"
+        ),
+        None => tracing::info!(
+            endpoint = endpoint::name_of(url),
+            parameters = %redacted_parameters(url),
+            %error,
+            "newsapi.stale_cache.miss"
+        ),
+    }
+}
+
+/// The key labelled `label` failed and the request is retried with the next one in the pool
+#[allow(unused_variables)]
+pub(crate) fn key_failover(label: &str, attempt: usize, error: &NewsApiError) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(key = label, attempt, %error, "newsapi.key_pool.failover");
+}
+
+/// A circuit breaker changed state
+#[allow(unused_variables)]
+pub(crate) fn circuit(state: CircuitState) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(state = ?state, "newsapi.circuit.transition");
+}
+
+/// NewsAPI refused a request because of the daily quota or rate limiting
+#[cfg(feature = "tracing")]
+fn quota(error: &NewsApiError) {
+    if let NewsApiError::TooManyRequests { .. } = error {
+        let code = error
+            .api_error()
+            .map(|error| error.code)
+            .unwrap_or_default();
+        tracing::warn!(%code, "newsapi.quota.refused");
+    }
+}
+
+/// A subscriber that keeps every span and event for tests to assert on
+#[cfg(all(test, feature = "tracing"))]
+pub(crate) mod capture {
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::Mutex;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    pub(crate) type Fields = HashMap<String, String>;
+
+    #[derive(Debug, Clone)]
+    pub(crate) struct CapturedSpan {
+        pub name: &'static str,
+        pub fields: Fields,
+    }
+
+    #[derive(Debug, Clone)]
+    pub(crate) struct CapturedEvent {
+        /// The span the event was emitted in, if any
+        pub span: Option<&'static str>,
+        pub fields: Fields,
+    }
+
+    #[derive(Debug, Default)]
+    pub(crate) struct Capture {
+        spans: Mutex<Vec<CapturedSpan>>,
+        events: Mutex<Vec<CapturedEvent>>,
+        entered: Mutex<Vec<u64>>,
+    }
+
+    impl Capture {
+        pub(crate) fn spans(&self) -> Vec<CapturedSpan> {
+            self.spans.lock().unwrap().clone()
+        }
+
+        pub(crate) fn events(&self) -> Vec<CapturedEvent> {
+            self.events.lock().unwrap().clone()
+        }
+    }
+
+    struct FieldVisitor<'a>(&'a mut Fields);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_owned(), value.to_owned());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.0.insert(field.name().to_owned(), format!("{value:?}"));
+        }
+    }
+
+    impl Subscriber for Capture {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+            let mut fields = Fields::new();
+            attributes.record(&mut FieldVisitor(&mut fields));
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(CapturedSpan {
+                name: attributes.metadata().name(),
+                fields,
+            });
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            let span = &mut spans[span.into_u64() as usize - 1];
+            values.record(&mut FieldVisitor(&mut span.fields));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::new();
+            event.record(&mut FieldVisitor(&mut fields));
+            let span = self.entered.lock().unwrap().last().map(|id| {
+                let spans = self.spans.lock().unwrap();
+                spans[*id as usize - 1].name
+            });
+            self.events
+                .lock()
+                .unwrap()
+                .push(CapturedEvent { span, fields });
+        }
+
+        fn enter(&self, span: &Id) {
+            self.entered.lock().unwrap().push(span.into_u64());
+        }
+
+        fn exit(&self, _: &Id) {
+            self.entered.lock().unwrap().pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redaction() {
+        assert_eq!(
+            redacted_parameters("https://newsapi.org/v2/everything?q=bitcoin&apiKey=123&page=2"),
+            "q=bitcoin&apiKey=REDACTED&page=2"
+        );
+        assert_eq!(redacted_parameters("https://newsapi.org/v2/sources"), "");
+    }
+
+    #[test]
+    fn fields() {
+        let url = "https://newsapi.org/v2/top-headlines?country=de&page=3";
+        assert_eq!(endpoint::name_of(url), "top-headlines");
+        assert_eq!(page(url), 3);
+        assert_eq!(page("https://newsapi.org/v2/sources"), 1);
+    }
+
+    #[cfg(all(feature = "tracing", any(feature = "async", feature = "blocking")))]
+    #[test]
+    fn events() {
+        use crate::api::NewsAPIClient;
+        use crate::circuit::CircuitBreaker;
+        use crate::constants;
+        use crate::key::{AuthMode, KeyPool};
+        use crate::transport::{HttpResponse, MockTransport, Route};
+        use capture::Capture;
+        use std::sync::Arc;
+
+        // the first key is rate limited, the second finds NewsAPI down, which opens the circuit
+        let rate_limited = include_str!("../resources/example_error_rate_limited.json");
+        let mock = Arc::new(
+            MockTransport::new()
+                .respond(
+                    Route::new(constants::SOURCES_URL).param("apiKey", "key-a"),
+                    HttpResponse::new(429, rate_limited),
+                )
+                .respond(
+                    Route::new(constants::SOURCES_URL).param("apiKey", "key-b"),
+                    HttpResponse::new(503, "Service Unavailable"),
+                ),
+        );
+        let breaker = CircuitBreaker::new().window(1).minimum_requests(1);
+
+        let capture = Arc::new(Capture::default());
+        let results = tracing::subscriber::with_default(capture.clone(), || {
+            let mut api = NewsAPIClient::new("unused".to_owned());
+            api.transport(mock.clone())
+                .auth_mode(AuthMode::QueryParameter)
+                .key_pool(Arc::new(KeyPool::new().key("a", "key-a").key("b", "key-b")))
+                .circuit_breaker(Arc::new(breaker));
+            let request = api.sources();
+            each_flavour!(request.send_async(), request.send_sync())
+        });
+        assert!(matches!(
+            results[0],
+            Err(NewsApiError::GenericError { code: 503, .. })
+        ));
+        for result in &results[1..] {
+            assert!(matches!(result, Err(NewsApiError::CircuitOpen)));
+        }
+        assert_eq!(mock.requests().len(), 2);
+
+        let events = capture.events();
+        let messages = |message: &str| {
+            events
+                .iter()
+                .filter(|event| event.fields["message"] == message)
+                .collect::<Vec<_>>()
+        };
+
+        let refused = messages("newsapi.quota.refused");
+        assert_eq!(refused.len(), 1);
+        assert_eq!(refused[0].fields["code"], "rateLimited");
+        assert_eq!(refused[0].span, Some("newsapi.request"));
+
+        let failovers = messages("newsapi.key_pool.failover");
+        assert_eq!(failovers.len(), 1);
+        assert_eq!(failovers[0].fields["key"], "a");
+        assert_eq!(failovers[0].fields["attempt"], "1");
+        assert!(failovers[0].fields["error"].starts_with("TooManyRequests"));
+
+        let transitions = messages("newsapi.circuit.transition");
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].fields["state"], "Open");
+    }
+}
@@ -0,0 +1,529 @@
+use super::error::NewsApiError;
+use super::key::{redact_header, redact_url};
+#[cfg(feature = "async")]
+use async_trait::async_trait;
+use percent_encoding::percent_decode_str;
+use std::fmt::{self, Debug};
+#[cfg(feature = "blocking")]
+use std::io::{Cursor, Read};
+use std::sync::Mutex;
+#[cfg(any(feature = "async", feature = "blocking"))]
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// A fully prepared request, ready to be sent by a transport. `Debug` redacts the API key.
+#[derive(Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    /// How long the transport may take over the whole request, if limited
+    pub timeout: Option<Duration>,
+}
+
+impl Debug for HttpRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let headers: Vec<_> = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name, redact_header(name, value)))
+            .collect();
+        f.debug_struct("HttpRequest")
+            .field("url", &redact_url(&self.url))
+            .field("headers", &headers)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl HttpRequest {
+    pub fn new(url: &str) -> HttpRequest {
+        HttpRequest {
+            url: url.to_owned(),
+            headers: vec![],
+            timeout: None,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> HttpRequest {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// The value of the first header called `name`, compared case insensitively
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+}
+
+/// What came back from the server, before any NewsAPI specific handling. `Debug` redacts the API
+/// key from the URL.
+#[derive(Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// The URL that produced this response, if the transport followed redirects
+    pub url: Option<String>,
+}
+
+impl Debug for HttpResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpResponse")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .field("body", &self.text())
+            .field("url", &self.url.as_deref().map(redact_url))
+            .finish()
+    }
+}
+
+impl HttpResponse {
+    pub fn new(status: u16, body: &str) -> HttpResponse {
+        HttpResponse {
+            status,
+            headers: vec![],
+            body: body.as_bytes().to_vec(),
+            url: None,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> HttpResponse {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// The value of the first header called `name`, compared case insensitively
+    pub fn header_value(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Sends requests for `send_async` and friends
+#[cfg(feature = "async")]
+#[async_trait]
+pub trait AsyncTransport: Debug + Send + Sync {
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError>;
+}
+
+/// A response whose body is read on demand, see `BlockingTransport::execute_reader`
+#[cfg(feature = "blocking")]
+pub struct HttpStream {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub url: Option<String>,
+    pub body: Box<dyn Read + Send>,
+}
+
+#[cfg(feature = "blocking")]
+impl HttpStream {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl Debug for HttpStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpStream")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .field("url", &self.url.as_deref().map(redact_url))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sends requests for `send_sync` and friends
+#[cfg(feature = "blocking")]
+pub trait BlockingTransport: Debug + Send + Sync {
+    fn execute_blocking(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError>;
+
+    /// Like `execute_blocking`, but the body can be consumed while it is still arriving. The
+    /// default implementation reads the whole body with `execute_blocking` first.
+    fn execute_reader(&self, request: HttpRequest) -> Result<HttpStream, NewsApiError> {
+        let resp = self.execute_blocking(request)?;
+        Ok(HttpStream {
+            status: resp.status,
+            headers: resp.headers,
+            url: resp.url,
+            body: Box::new(Cursor::new(resp.body)),
+        })
+    }
+}
+
+/// A transport for every flavour enabled through cargo features; implemented automatically for
+/// any type that provides them.
+#[cfg(all(feature = "async", feature = "blocking"))]
+pub trait Transport: AsyncTransport + BlockingTransport {}
+#[cfg(all(feature = "async", feature = "blocking"))]
+impl<T: AsyncTransport + BlockingTransport> Transport for T {}
+
+#[cfg(all(feature = "async", not(feature = "blocking")))]
+pub trait Transport: AsyncTransport {}
+#[cfg(all(feature = "async", not(feature = "blocking")))]
+impl<T: AsyncTransport> Transport for T {}
+
+#[cfg(all(not(feature = "async"), feature = "blocking"))]
+pub trait Transport: BlockingTransport {}
+#[cfg(all(not(feature = "async"), feature = "blocking"))]
+impl<T: BlockingTransport> Transport for T {}
+
+#[cfg(not(any(feature = "async", feature = "blocking")))]
+pub trait Transport: Debug + Send + Sync {}
+#[cfg(not(any(feature = "async", feature = "blocking")))]
+impl<T: Debug + Send + Sync> Transport for T {}
+
+/// The default transport. The underlying reqwest clients are created on first use and reused
+/// for every request after that.
+#[derive(Debug, Default)]
+pub struct ReqwestTransport {
+    #[cfg(feature = "async")]
+    client: OnceLock<reqwest::Client>,
+    #[cfg(feature = "blocking")]
+    blocking_client: OnceLock<reqwest::blocking::Client>,
+}
+
+impl ReqwestTransport {
+    pub fn new() -> ReqwestTransport {
+        ReqwestTransport::default()
+    }
+
+    #[cfg(feature = "async")]
+    fn client(&self) -> Result<&reqwest::Client, NewsApiError> {
+        if let Some(client) = self.client.get() {
+            return Ok(client);
+        }
+        let client = reqwest::Client::builder().build()?;
+        Ok(self.client.get_or_init(|| client))
+    }
+
+    #[cfg(feature = "blocking")]
+    fn blocking_client(&self) -> Result<&reqwest::blocking::Client, NewsApiError> {
+        if let Some(client) = self.blocking_client.get() {
+            return Ok(client);
+        }
+        let client = reqwest::blocking::Client::builder().build()?;
+        Ok(self.blocking_client.get_or_init(|| client))
+    }
+
+    #[cfg(feature = "blocking")]
+    fn send_blocking(
+        &self,
+        request: &HttpRequest,
+    ) -> Result<reqwest::blocking::Response, NewsApiError> {
+        with_request(self.blocking_client()?.get(&request.url), request)
+            .send()
+            .map_err(request_error)
+    }
+}
+
+/// The parts of the async and blocking reqwest request builders `with_request` needs
+#[cfg(any(feature = "async", feature = "blocking"))]
+trait RequestBuilder: Sized {
+    fn header(self, name: &str, value: &str) -> Self;
+    fn timeout(self, timeout: Duration) -> Self;
+}
+
+#[cfg(feature = "async")]
+impl RequestBuilder for reqwest::RequestBuilder {
+    fn header(self, name: &str, value: &str) -> Self {
+        self.header(name, value)
+    }
+
+    fn timeout(self, timeout: Duration) -> Self {
+        self.timeout(timeout)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl RequestBuilder for reqwest::blocking::RequestBuilder {
+    fn header(self, name: &str, value: &str) -> Self {
+        self.header(name, value)
+    }
+
+    fn timeout(self, timeout: Duration) -> Self {
+        self.timeout(timeout)
+    }
+}
+
+/// Add the headers and timeout of `request` to `builder`
+#[cfg(any(feature = "async", feature = "blocking"))]
+fn with_request<B: RequestBuilder>(mut builder: B, request: &HttpRequest) -> B {
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Some(timeout) = request.timeout {
+        builder = builder.timeout(timeout);
+    }
+    builder
+}
+
+/// Map a reqwest failure, keeping the API key out of the URL reqwest attaches to its errors
+#[cfg(any(feature = "async", feature = "blocking"))]
+fn request_error(mut error: reqwest::Error) -> NewsApiError {
+    if error.is_timeout() {
+        return NewsApiError::Timeout;
+    }
+    if let Some(url) = error.url_mut() {
+        if let Ok(redacted) = reqwest::Url::parse(&redact_url(url.as_str())) {
+            *url = redacted;
+        }
+    }
+    NewsApiError::from(error)
+}
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+fn collect_headers(headers: &reqwest::header::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.as_str().to_owned(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncTransport for ReqwestTransport {
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError> {
+        let resp = with_request(self.client()?.get(&request.url), &request)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let status = resp.status().as_u16();
+        let headers = collect_headers(resp.headers());
+        let url = Some(resp.url().to_string());
+        let body = resp.bytes().await.map_err(request_error)?.to_vec();
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+            url,
+        })
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl BlockingTransport for ReqwestTransport {
+    fn execute_blocking(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError> {
+        let resp = self.send_blocking(&request)?;
+        let status = resp.status().as_u16();
+        let headers = collect_headers(resp.headers());
+        let url = Some(resp.url().to_string());
+        let body = resp.bytes().map_err(request_error)?.to_vec();
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+            url,
+        })
+    }
+
+    fn execute_reader(&self, request: HttpRequest) -> Result<HttpStream, NewsApiError> {
+        let resp = self.send_blocking(&request)?;
+        Ok(HttpStream {
+            status: resp.status().as_u16(),
+            headers: collect_headers(resp.headers()),
+            url: Some(resp.url().to_string()),
+            body: Box::new(resp),
+        })
+    }
+}
+
+/// Matches requests on their base URL and, optionally, a subset of their query parameters
+#[derive(Debug, Clone)]
+pub struct Route {
+    base_url: String,
+    params: Vec<(String, String)>,
+}
+
+impl Route {
+    /// Match requests to `base_url`, e.g. `constants::EVERYTHING_URL`
+    pub fn new(base_url: &str) -> Route {
+        Route {
+            base_url: base_url.to_owned(),
+            params: vec![],
+        }
+    }
+
+    /// Additionally require the (decoded) query parameter `name` to equal `value`
+    pub fn param(mut self, name: &str, value: &str) -> Route {
+        self.params.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        let (base_url, query) = url.split_once('?').unwrap_or((url, ""));
+        if base_url != self.base_url {
+            return false;
+        }
+
+        let params = query_params(query);
+        self.params.iter().all(|wanted| params.contains(wanted))
+    }
+}
+
+fn query_params(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (
+                percent_decode_str(name).decode_utf8_lossy().into_owned(),
+                percent_decode_str(value).decode_utf8_lossy().into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// A transport for tests that returns canned responses and records every request it sees.
+/// Routes are tried in the order they were added; unmatched requests fail with a
+/// `TransportError`.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    routes: Mutex<Vec<(Route, HttpResponse)>>,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl MockTransport {
+    pub fn new() -> MockTransport {
+        MockTransport::default()
+    }
+
+    /// Answer requests matching `route` with `response`
+    pub fn respond(self, route: Route, response: HttpResponse) -> MockTransport {
+        self.routes.lock().unwrap().push((route, response));
+        self
+    }
+
+    /// Every request sent so far, oldest first
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn handle(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError> {
+        let response = self
+            .routes
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(route, _)| route.matches(&request.url))
+            .map(|(_, response)| response.clone());
+
+        let url = redact_url(&request.url);
+        self.requests.lock().unwrap().push(request);
+
+        response.ok_or_else(|| NewsApiError::TransportError {
+            message: format!("no mock route matches {url}"),
+        })
+    }
+}
+
+#[cfg(feature = "async")]
+#[async_trait]
+impl AsyncTransport for MockTransport {
+    async fn execute(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError> {
+        self.handle(request)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl BlockingTransport for MockTransport {
+    fn execute_blocking(&self, request: HttpRequest) -> Result<HttpResponse, NewsApiError> {
+        self.handle(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants;
+
+    #[test]
+    fn route_matches() {
+        let route = Route::new(constants::EVERYTHING_URL).param("q", "bitcoin price");
+
+        assert!(route.matches("https://newsapi.org/v2/everything?q=bitcoin%20price&page=2"));
+        assert!(!route.matches("https://newsapi.org/v2/everything?q=bitcoin"));
+        assert!(!route.matches("https://newsapi.org/v2/top-headlines?q=bitcoin%20price"));
+        assert!(Route::new(constants::SOURCES_URL).matches(constants::SOURCES_URL));
+    }
+
+    #[test]
+    fn response_debug_redacts_key() {
+        let url = "https://newsapi.org/v2/everything?q=a&apiKey=s3cret";
+        let response = HttpResponse {
+            url: Some(url.to_owned()),
+            ..HttpResponse::new(200, "{}")
+        };
+        let debug = format!("{response:?}");
+        assert!(!debug.contains("s3cret"));
+        assert!(debug.contains("apiKey=REDACTED"));
+
+        #[cfg(feature = "blocking")]
+        {
+            let stream = HttpStream {
+                status: 200,
+                headers: vec![],
+                url: Some(url.to_owned()),
+                body: Box::new(Cursor::new(vec![])),
+            };
+            assert!(!format!("{stream:?}").contains("s3cret"));
+        }
+    }
+
+    #[test]
+    fn transport_follows_features() {
+        fn assert_transport<T: Transport>() {}
+        // the built-in transports provide every flavour the enabled features need
+        assert_transport::<ReqwestTransport>();
+        assert_transport::<MockTransport>();
+
+        // with neither flavour enabled there is nothing to implement
+        #[cfg(not(any(feature = "async", feature = "blocking")))]
+        {
+            #[derive(Debug)]
+            struct Nothing;
+            assert_transport::<Nothing>();
+        }
+    }
+
+    #[test]
+    fn mock_records_requests() {
+        let mock = MockTransport::new().respond(
+            Route::new(constants::SOURCES_URL),
+            HttpResponse::new(200, "{}"),
+        );
+
+        let request = HttpRequest::new(constants::SOURCES_URL).header("X-Api-Key", "123");
+        let response = mock.handle(request.clone()).unwrap();
+        assert_eq!(response.text(), "{}");
+
+        let unmatched = mock.handle(HttpRequest::new(constants::EVERYTHING_URL));
+        assert!(unmatched.is_err());
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].header_value("x-api-key"), Some("123"));
+    }
+}
@@ -0,0 +1,404 @@
+use super::endpoint::Endpoint;
+use super::error::NewsApiError;
+use super::key::until_midnight_utc;
+use super::payload::article::{Article, Articles};
+use super::request::Request;
+use super::timer::sleep_until;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Failed polls double the delay before the next one, up to this many times
+const MAX_BACKOFF_DOUBLINGS: u32 = 4;
+
+/// Polls a request on an interval and yields the articles that were not in earlier responses,
+/// oldest first, from `next`. Made with `Request::watch`, e.g.
+/// `client.country(Country::UnitedKingdom).top_headlines().watch()`.
+///
+/// Articles are told apart by URL. The URLs seen most recently are kept in memory, up to
+/// `capacity`, and can be saved to a file with `seen_file` so a restart does not report them
+/// again. When the client's metrics track a daily quota, polls are spread out so the quota
+/// left lasts until it resets at midnight UTC.
+///
+/// Waiting between polls does not depend on an async runtime; one timer thread shared by every
+/// watch wakes the task when the next poll is due.
+///
+/// A watch is pulled with `next` rather than being a `futures::Stream`: the crate does not
+/// depend on `futures`, and a `Stream` would have to own the send in flight, which borrows
+/// the watch's request. Where a stream is needed, `futures::stream::unfold` over the watch and
+/// `next` makes one.
+#[derive(Debug)]
+pub struct Watch<'a, E> {
+    request: Request<'a, E>,
+    interval: Duration,
+    jitter: Option<Duration>,
+    skip_initial: bool,
+    seen: SeenSet,
+    seen_file: Option<PathBuf>,
+    loaded: bool,
+    polled: bool,
+    failures: u32,
+    next_poll: Option<Instant>,
+    ready: VecDeque<Article>,
+}
+
+impl<'a, E> Watch<'a, E>
+where
+    E: Endpoint<Payload = Articles>,
+{
+    /// Poll every minute, remembering the last 10,000 articles
+    pub(crate) fn new(request: Request<'a, E>) -> Watch<'a, E> {
+        Watch {
+            request,
+            interval: Duration::from_secs(60),
+            jitter: None,
+            skip_initial: false,
+            seen: SeenSet::new(10_000),
+            seen_file: None,
+            loaded: false,
+            polled: false,
+            failures: 0,
+            next_poll: None,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Time between polls
+    pub fn interval(mut self, interval: Duration) -> Watch<'a, E> {
+        self.interval = interval;
+        self
+    }
+
+    /// Add a random delay of up to `jitter` to each interval, a tenth of the interval unless
+    /// set, so many watchers do not poll in lockstep
+    pub fn jitter(mut self, jitter: Duration) -> Watch<'a, E> {
+        self.jitter = Some(jitter);
+        self
+    }
+
+    /// How many article URLs to remember. The oldest are forgotten first, so an article that
+    /// drops out of the results and comes back much later is reported again.
+    pub fn capacity(mut self, capacity: usize) -> Watch<'a, E> {
+        self.seen = SeenSet::new(capacity);
+        self
+    }
+
+    /// Load the seen article URLs from `path` before the first poll, if it exists, and save
+    /// them there whenever new articles turn up
+    pub fn seen_file(mut self, path: impl Into<PathBuf>) -> Watch<'a, E> {
+        self.seen_file = Some(path.into());
+        self
+    }
+
+    /// Treat the articles of the first poll as seen rather than new, unless seen articles
+    /// were loaded from `seen_file`. Useful for alerts, which should only fire for articles
+    /// published after the watch started.
+    pub fn skip_initial(mut self) -> Watch<'a, E> {
+        self.skip_initial = true;
+        self
+    }
+
+    /// The next new article, polling NewsAPI as often as needed to find one. A failed poll
+    /// returns its error; calling `next` again carries on watching after a longer delay.
+    pub async fn next(&mut self) -> Result<Article, NewsApiError> {
+        loop {
+            if let Some(article) = self.ready.pop_front() {
+                return Ok(article);
+            }
+            if let Some(at) = self.next_poll {
+                sleep_until(at).await;
+            }
+            self.poll().await?;
+        }
+    }
+
+    async fn poll(&mut self) -> Result<(), NewsApiError> {
+        if !self.loaded {
+            if let Some(path) = &self.seen_file {
+                self.seen.load(path)?;
+            }
+            self.loaded = true;
+        }
+
+        let result = self.request.send_async().await;
+        self.failures = if result.is_ok() { 0 } else { self.failures + 1 };
+        self.next_poll = Some(Instant::now() + self.delay());
+        let articles = result?.articles;
+
+        let seeding = self.skip_initial && !self.polled && self.seen.is_empty();
+        self.polled = true;
+        let mut fresh: Vec<Article> = articles
+            .into_iter()
+            .filter(|article| self.seen.insert(&article.url))
+            .collect();
+        if fresh.is_empty() {
+            return Ok(());
+        }
+        if !seeding {
+            // NewsAPI lists the newest first
+            fresh.reverse();
+            self.ready.extend(fresh);
+        }
+        match &self.seen_file {
+            Some(path) => self.seen.save(path),
+            None => Ok(()),
+        }
+    }
+
+    /// How long to wait before the next poll
+    fn delay(&self) -> Duration {
+        let jitter = self.jitter.unwrap_or(self.interval / 10);
+        let mut delay = (self.interval + random_up_to(jitter))
+            * 2u32.pow(self.failures.min(MAX_BACKOFF_DOUBLINGS));
+
+        if let Some(remaining) = self.request.client().quota_remaining() {
+            let until_reset = until_midnight_utc();
+            delay = delay.max(match remaining {
+                0 => until_reset,
+                remaining => until_reset / remaining,
+            });
+        }
+        delay
+    }
+}
+
+/// The most recently seen article URLs, up to a fixed number
+#[derive(Debug)]
+struct SeenSet {
+    capacity: usize,
+    order: VecDeque<String>,
+    urls: HashSet<String>,
+}
+
+impl SeenSet {
+    fn new(capacity: usize) -> SeenSet {
+        SeenSet {
+            capacity: capacity.max(1),
+            order: VecDeque::new(),
+            urls: HashSet::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Remember `url`, returning true if it was not seen before
+    fn insert(&mut self, url: &str) -> bool {
+        if self.urls.contains(url) {
+            return false;
+        }
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.urls.remove(&oldest);
+            }
+        }
+        self.order.push_back(url.to_owned());
+        self.urls.insert(url.to_owned());
+        true
+    }
+
+    /// Add the URLs in `path`, one per line and oldest first. A missing file is an empty set.
+    fn load(&mut self, path: &Path) -> Result<(), NewsApiError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(state_error(path, error)),
+        };
+        for url in text.lines().filter(|line| !line.is_empty()) {
+            self.insert(url);
+        }
+        Ok(())
+    }
+
+    /// Write the URLs to `path` through a temporary file, so a crash cannot leave it half
+    /// written
+    fn save(&self, path: &Path) -> Result<(), NewsApiError> {
+        let mut text = String::new();
+        for url in &self.order {
+            text.push_str(url);
+            text.push('\n');
+        }
+        let partial = partial_path(path);
+        fs::write(&partial, text).map_err(|error| state_error(&partial, error))?;
+        fs::rename(&partial, path).map_err(|error| state_error(path, error))
+    }
+}
+
+/// `path` with `.partial` added to the whole file name, so `seen.txt` and `seen.json` do not
+/// share a temporary file
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+fn state_error(path: &Path, error: std::io::Error) -> NewsApiError {
+    NewsApiError::WatchStateError {
+        message: format!("{}: {error}", path.display()),
+    }
+}
+
+/// A duration between zero and `max`, random enough to spread polls out
+fn random_up_to(max: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    max.mul_f64(random as f64 / u64::MAX as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::NewsAPIClient;
+    use crate::key::KeyPool;
+    use crate::metrics::Metrics;
+    use crate::middleware::Flow;
+    use crate::testing::{articles_page, scripted_client};
+    use crate::transport::HttpResponse;
+    use std::env;
+    use std::sync::Arc;
+
+    /// A top headlines response listing `urls`, newest first
+    fn page(urls: &[&str]) -> HttpResponse {
+        let articles: Vec<_> = urls
+            .iter()
+            .map(|url| (*url, "2022-06-18T12:00:00Z"))
+            .collect();
+        HttpResponse::new(200, &articles_page(&articles, urls.len()))
+    }
+
+    async fn titles<E>(watch: &mut Watch<'_, E>, count: usize) -> Vec<String>
+    where
+        E: Endpoint<Payload = Articles>,
+    {
+        let mut titles = vec![];
+        for _ in 0..count {
+            titles.push(watch.next().await.unwrap().title.unwrap());
+        }
+        titles
+    }
+
+    #[tokio::test]
+    async fn yields_new_articles() {
+        let (api, _) = scripted_client(vec![
+            page(&["b", "a"]),
+            page(&["b", "a"]),
+            page(&["d", "c", "b"]),
+        ]);
+        let mut watch = api
+            .top_headlines()
+            .watch()
+            .interval(Duration::from_millis(5));
+
+        assert_eq!(titles(&mut watch, 2).await, ["a", "b"]);
+        // the second page has nothing new, so the third is fetched
+        assert_eq!(titles(&mut watch, 2).await, ["c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn skip_initial() {
+        let (api, _) = scripted_client(vec![page(&["b", "a"]), page(&["c", "b", "a"])]);
+        let mut watch = api
+            .top_headlines()
+            .watch()
+            .interval(Duration::ZERO)
+            .skip_initial();
+        assert_eq!(titles(&mut watch, 1).await, ["c"]);
+    }
+
+    #[tokio::test]
+    async fn seen_file() {
+        let path = env::temp_dir().join(format!("newsapi-watch-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let (api, _) = scripted_client(vec![page(&["b", "a"])]);
+        let mut watch = api.top_headlines().watch().seen_file(&path);
+        assert_eq!(titles(&mut watch, 2).await, ["a", "b"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "b\na\n");
+
+        // a restart only reports what was not seen before, even with skip_initial
+        let (api, _) = scripted_client(vec![page(&["c", "b", "a"])]);
+        let mut watch = api.top_headlines().watch().seen_file(&path).skip_initial();
+        assert_eq!(titles(&mut watch, 1).await, ["c"]);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "b\na\nc\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn next_is_send() {
+        fn assert_send<T: Send>(_: T) {}
+        let api = NewsAPIClient::new("123".to_owned());
+        let mut watch = api.top_headlines().watch();
+        assert_send(watch.next());
+    }
+
+    #[test]
+    fn partial_path_keeps_the_name() {
+        assert_eq!(
+            partial_path(Path::new("state/seen.txt")),
+            Path::new("state/seen.txt.partial")
+        );
+        assert_ne!(
+            partial_path(Path::new("seen.txt")),
+            partial_path(Path::new("seen.json"))
+        );
+    }
+
+    #[test]
+    fn bounded() {
+        let mut seen = SeenSet::new(2);
+        assert!(seen.insert("a"));
+        assert!(seen.insert("b"));
+        assert!(!seen.insert("a"));
+        assert!(seen.insert("c"));
+        // "a" was forgotten to make room for "c"
+        assert!(seen.insert("a"));
+        assert!(!seen.insert("c"));
+        assert_eq!(seen.urls.len(), 2);
+    }
+
+    #[test]
+    fn delay() {
+        let mut api = NewsAPIClient::new("123".to_owned());
+        let watch = api
+            .top_headlines()
+            .watch()
+            .interval(Duration::from_secs(60))
+            .jitter(Duration::from_secs(10));
+        let delay = watch.delay();
+        assert!(delay >= Duration::from_secs(60) && delay <= Duration::from_secs(70));
+
+        // with no quota left, wait for it to reset
+        api.metrics(Arc::new(Metrics::with_daily_quota(0)));
+        let watch = api.top_headlines().watch().interval(Duration::ZERO);
+        let delay = watch.delay();
+        assert!(delay > Duration::ZERO && delay <= Duration::from_secs(24 * 60 * 60));
+    }
+
+    #[tokio::test]
+    async fn delay_after_key_failover() {
+        let exhausted = r#"{"status": "error", "code": "apiKeyExhausted", "message": "done"}"#;
+        let page = page(&["a"]);
+        let mut api = NewsAPIClient::new("unused".to_owned());
+        api.key_pool(Arc::new(
+            KeyPool::new().key("spent", "key-a").key("fresh", "key-b"),
+        ))
+        .metrics(Arc::new(Metrics::with_daily_quota(1_000_000)))
+        .before_request(move |request| match request.header_value("X-Api-Key") {
+            Some("key-a") => Ok(Flow::Respond(HttpResponse::new(429, exhausted))),
+            _ => Ok(Flow::Respond(page.clone())),
+        });
+        api.top_headlines().send_async().await.unwrap();
+
+        // the other key still has requests left, so the watch keeps its interval
+        let watch = api
+            .top_headlines()
+            .watch()
+            .interval(Duration::from_secs(60))
+            .jitter(Duration::from_secs(10));
+        let delay = watch.delay();
+        assert!(delay >= Duration::from_secs(60) && delay <= Duration::from_secs(70));
+    }
+}